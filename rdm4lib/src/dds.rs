@@ -0,0 +1,849 @@
+//! Decoding and encoding of the DDS textures used by Anno. Only the top mip level is read.
+
+use binrw::{binrw, BinReaderExt, BinWriterExt};
+use nalgebra::{Matrix3, Vector3};
+use std::{io, path::Path};
+
+use crate::rdm_error::RdmError;
+
+/// `DDS_PIXELFORMAT::flags`
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+
+/// `DDS_HEADER::flags`: caps, height, width, pixel format, mip map count and linear size.
+const DDSD_BLOCK_COMPRESSED: u32 = 0x1 | 0x2 | 0x4 | 0x1000 | 0x20000 | 0x80000;
+/// `DDS_HEADER::caps`: complex, texture and mip map.
+const DDSCAPS_MIPMAPPED_TEXTURE: u32 = 0x8 | 0x1000 | 0x400000;
+
+#[binrw]
+#[brw(little)]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DdsPixelFormat {
+    pub size: u32,
+    pub flags: u32,
+    pub four_cc: [u8; 4],
+    pub rgb_bit_count: u32,
+    pub r_bit_mask: u32,
+    pub g_bit_mask: u32,
+    pub b_bit_mask: u32,
+    pub a_bit_mask: u32,
+}
+
+#[binrw]
+#[brw(little)]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DdsHeaderDx10 {
+    pub dxgi_format: u32,
+    pub resource_dimension: u32,
+    pub misc_flag: u32,
+    pub array_size: u32,
+    pub misc_flags2: u32,
+}
+
+#[binrw]
+#[brw(little, magic = b"DDS ")]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DdsHeader {
+    pub size: u32,
+    pub flags: u32,
+    pub height: u32,
+    pub width: u32,
+    pub pitch_or_linear_size: u32,
+    pub depth: u32,
+    pub mip_map_count: u32,
+    pub reserved1: [u32; 11],
+    pub pixel_format: DdsPixelFormat,
+    pub caps: u32,
+    pub caps2: u32,
+    pub caps3: u32,
+    pub caps4: u32,
+    pub reserved2: u32,
+    #[br(if(pixel_format.flags & DDPF_FOURCC != 0 && pixel_format.four_cc == *b"DX10"))]
+    pub dx10: Option<DdsHeaderDx10>,
+}
+
+/// Block compressed and uncompressed formats that can be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFormat {
+    Bc1,
+    Bc2,
+    Bc3,
+    Bc4,
+    Bc5,
+    Bc7,
+    Rgba8,
+    Bgra8,
+    Bgrx8,
+    /// Uncompressed pixels described by the bit masks of the legacy pixel format.
+    Masked {
+        bytes_per_pixel: u32,
+        masks: [u32; 4],
+    },
+}
+
+impl TextureFormat {
+    fn from_header(header: &DdsHeader) -> Result<Self, RdmError> {
+        let pf = &header.pixel_format;
+        if let Some(dx10) = &header.dx10 {
+            return match dx10.dxgi_format {
+                71 | 72 => Ok(TextureFormat::Bc1),
+                74 | 75 => Ok(TextureFormat::Bc2),
+                77 | 78 => Ok(TextureFormat::Bc3),
+                80 => Ok(TextureFormat::Bc4),
+                83 => Ok(TextureFormat::Bc5),
+                98 | 99 => Ok(TextureFormat::Bc7),
+                28 | 29 => Ok(TextureFormat::Rgba8),
+                87 | 91 => Ok(TextureFormat::Bgra8),
+                88 | 93 => Ok(TextureFormat::Bgrx8),
+                f => Err(RdmError::UnsupportedTextureFormat(format!(
+                    "DXGI format {}",
+                    f
+                ))),
+            };
+        }
+        if pf.flags & DDPF_FOURCC != 0 {
+            return match &pf.four_cc {
+                b"DXT1" => Ok(TextureFormat::Bc1),
+                b"DXT2" | b"DXT3" => Ok(TextureFormat::Bc2),
+                b"DXT4" | b"DXT5" => Ok(TextureFormat::Bc3),
+                b"ATI1" | b"BC4U" => Ok(TextureFormat::Bc4),
+                b"ATI2" | b"BC5U" => Ok(TextureFormat::Bc5),
+                f => Err(RdmError::UnsupportedTextureFormat(format!(
+                    "FourCC {}",
+                    String::from_utf8_lossy(f)
+                ))),
+            };
+        }
+        if pf.flags & DDPF_RGB != 0 && (pf.rgb_bit_count == 24 || pf.rgb_bit_count == 32) {
+            let a_mask = if pf.flags & DDPF_ALPHAPIXELS != 0 {
+                pf.a_bit_mask
+            } else {
+                0
+            };
+            return Ok(TextureFormat::Masked {
+                bytes_per_pixel: pf.rgb_bit_count / 8,
+                masks: [pf.r_bit_mask, pf.g_bit_mask, pf.b_bit_mask, a_mask],
+            });
+        }
+        Err(RdmError::UnsupportedTextureFormat(format!(
+            "pixel format flags {:#x}, {} bit",
+            pf.flags, pf.rgb_bit_count
+        )))
+    }
+
+    /// Bytes per 4x4 block for block compressed formats.
+    fn block_size(&self) -> Option<usize> {
+        match self {
+            TextureFormat::Bc1 | TextureFormat::Bc4 => Some(8),
+            TextureFormat::Bc2 | TextureFormat::Bc3 | TextureFormat::Bc5 | TextureFormat::Bc7 => {
+                Some(16)
+            }
+            _ => None,
+        }
+    }
+
+    fn bytes_per_pixel(&self) -> usize {
+        match self {
+            TextureFormat::Masked {
+                bytes_per_pixel, ..
+            } => *bytes_per_pixel as usize,
+            _ => 4,
+        }
+    }
+}
+
+/// Top mip level of a DDS texture as 8 bit RGBA.
+#[derive(Debug, Clone)]
+pub struct DdsImage {
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    pub rgba: Vec<u8>,
+}
+
+impl DdsImage {
+    pub fn try_from_path<P: AsRef<Path>>(f_path: P) -> Result<Self, RdmError> {
+        let buffer = std::fs::read(&f_path)?;
+        DdsImage::try_from_bytes(&buffer)
+    }
+
+    pub fn try_from_bytes(buffer: &[u8]) -> Result<Self, RdmError> {
+        let mut reader = io::Cursor::new(buffer);
+        let header: DdsHeader = reader.read_le()?;
+        let format = TextureFormat::from_header(&header)?;
+        let data = &buffer[reader.position() as usize..];
+
+        let (width, height) = (header.width as usize, header.height as usize);
+        let needed = match format.block_size() {
+            Some(block_size) => width.div_ceil(4) * height.div_ceil(4) * block_size,
+            None => width * height * format.bytes_per_pixel(),
+        };
+        if data.len() < needed {
+            return Err(RdmError::Parse {
+                pos: buffer.len() as u64,
+                message: format!(
+                    "DDS data too short: {} bytes for {}x{} {:?}",
+                    data.len(),
+                    width,
+                    height,
+                    format
+                ),
+            });
+        }
+
+        let rgba = match format.block_size() {
+            Some(block_size) => decode_blocks(&data[..needed], width, height, block_size, format),
+            None => decode_uncompressed(&data[..needed], format),
+        };
+
+        Ok(DdsImage {
+            width: header.width,
+            height: header.height,
+            format,
+            rgba,
+        })
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>, RdmError> {
+        let mut png_buffer = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png_buffer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(io::Error::from)?;
+        writer
+            .write_image_data(&self.rgba)
+            .map_err(io::Error::from)?;
+        writer.finish().map_err(io::Error::from)?;
+        Ok(png_buffer)
+    }
+
+    pub fn from_rgba(width: u32, height: u32, rgba: Vec<u8>) -> Self {
+        assert_eq!(rgba.len(), (width * height * 4) as usize);
+        DdsImage {
+            width,
+            height,
+            format: TextureFormat::Rgba8,
+            rgba,
+        }
+    }
+
+    /// Encodes BC1 (DXT1), or BC3 (DXT5) if any texel is translucent, with a full mip chain.
+    pub fn to_dds(&self) -> Result<Vec<u8>, RdmError> {
+        let format = if self.rgba.chunks_exact(4).any(|p| p[3] != 255) {
+            TextureFormat::Bc3
+        } else {
+            TextureFormat::Bc1
+        };
+
+        let (mut width, mut height) = (self.width as usize, self.height as usize);
+        let mut mip = self.rgba.clone();
+        let mut data = encode_blocks(&mip, width, height, format);
+        let linear_size = data.len() as u32;
+        let mut mip_map_count = 1;
+        while width > 1 || height > 1 {
+            (mip, width, height) = downsample(&mip, width, height);
+            data.extend(encode_blocks(&mip, width, height, format));
+            mip_map_count += 1;
+        }
+
+        let header = DdsHeader {
+            size: 124,
+            flags: DDSD_BLOCK_COMPRESSED,
+            height: self.height,
+            width: self.width,
+            pitch_or_linear_size: linear_size,
+            mip_map_count,
+            pixel_format: DdsPixelFormat {
+                size: 32,
+                flags: DDPF_FOURCC,
+                four_cc: if format == TextureFormat::Bc1 {
+                    *b"DXT1"
+                } else {
+                    *b"DXT5"
+                },
+                ..Default::default()
+            },
+            caps: DDSCAPS_MIPMAPPED_TEXTURE,
+            ..Default::default()
+        };
+
+        let mut writer = io::Cursor::new(Vec::with_capacity(128 + data.len()));
+        writer.write_le(&header)?;
+        let mut buffer = writer.into_inner();
+        buffer.extend(data);
+        Ok(buffer)
+    }
+}
+
+/// Next mip level: 2x2 box filter, odd edges reuse the last row or column.
+fn downsample(rgba: &[u8], width: usize, height: usize) -> (Vec<u8>, usize, usize) {
+    let (dst_width, dst_height) = ((width / 2).max(1), (height / 2).max(1));
+    let mut dst = Vec::with_capacity(dst_width * dst_height * 4);
+    for y in 0..dst_height {
+        for x in 0..dst_width {
+            let (x0, y0) = (2 * x, 2 * y);
+            let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+            for c in 0..4 {
+                let sum: u32 = [(x0, y0), (x1, y0), (x0, y1), (x1, y1)]
+                    .iter()
+                    .map(|(sx, sy)| rgba[(sy * width + sx) * 4 + c] as u32)
+                    .sum();
+                dst.push(((sum + 2) / 4) as u8);
+            }
+        }
+    }
+    (dst, dst_width, dst_height)
+}
+
+fn encode_blocks(rgba: &[u8], width: usize, height: usize, format: TextureFormat) -> Vec<u8> {
+    let block_size = format.block_size().unwrap();
+    let (blocks_x, blocks_y) = (width.div_ceil(4), height.div_ceil(4));
+    let mut data = Vec::with_capacity(blocks_x * blocks_y * block_size);
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            // blocks at the right and bottom edge repeat the last texel
+            let mut texels: Texels = [[0; 4]; 16];
+            for (j, texel) in texels.iter_mut().enumerate() {
+                let x = (bx * 4 + j % 4).min(width - 1);
+                let y = (by * 4 + j / 4).min(height - 1);
+                let src = (y * width + x) * 4;
+                texel.copy_from_slice(&rgba[src..src + 4]);
+            }
+            if format == TextureFormat::Bc3 {
+                data.extend(encode_bc4_channel(texels.map(|t| t[3])));
+            }
+            data.extend(encode_bc1_color(&texels));
+        }
+    }
+    data
+}
+
+fn to_rgb565(c: &Vector3<f32>) -> u16 {
+    let q = |v: f32, max: f32| (v.clamp(0.0, 255.0) / 255.0 * max).round() as u16;
+    (q(c.x, 31.0) << 11) | (q(c.y, 63.0) << 5) | q(c.z, 31.0)
+}
+
+/// Endpoints along the principal axis of the block colours, always in four colour mode.
+fn encode_bc1_color(texels: &Texels) -> [u8; 8] {
+    let colors = texels.map(|t| Vector3::new(t[0] as f32, t[1] as f32, t[2] as f32));
+    let mean = colors.iter().sum::<Vector3<f32>>() / 16.0;
+    let covariance = colors
+        .iter()
+        .map(|c| (c - mean) * (c - mean).transpose())
+        .sum::<Matrix3<f32>>();
+
+    let mut axis = Vector3::new(1.0, 1.0, 1.0);
+    for _ in 0..8 {
+        axis = covariance * axis;
+        let norm = axis.norm();
+        if norm < f32::EPSILON {
+            break;
+        }
+        axis /= norm;
+    }
+    if axis.norm() < f32::EPSILON {
+        axis = Vector3::zeros();
+    }
+
+    let (lo, hi) = colors.iter().fold((0.0f32, 0.0f32), |(lo, hi), c| {
+        let d = (c - mean).dot(&axis);
+        (lo.min(d), hi.max(d))
+    });
+    let mut c0 = to_rgb565(&(mean + axis * hi));
+    let mut c1 = to_rgb565(&(mean + axis * lo));
+    if c0 < c1 {
+        std::mem::swap(&mut c0, &mut c1);
+    }
+
+    let mut indices = 0u32;
+    // c0 == c1 would select the three colour mode in BC1: only index 0 is safe
+    if c0 != c1 {
+        let palette = bc1_palette(c0, c1, false);
+        for (i, t) in texels.iter().enumerate() {
+            let best = (0..4)
+                .min_by_key(|&k| {
+                    (0..3)
+                        .map(|c| (t[c] as i32 - palette[k][c] as i32).pow(2))
+                        .sum::<i32>()
+                })
+                .unwrap();
+            indices |= (best as u32) << (2 * i);
+        }
+    }
+
+    let mut block = [0; 8];
+    block[0..2].copy_from_slice(&c0.to_le_bytes());
+    block[2..4].copy_from_slice(&c1.to_le_bytes());
+    block[4..8].copy_from_slice(&indices.to_le_bytes());
+    block
+}
+
+/// Eight value mode between the extremes of the block.
+fn encode_bc4_channel(values: [u8; 16]) -> [u8; 8] {
+    let a0 = *values.iter().max().unwrap();
+    let a1 = *values.iter().min().unwrap();
+
+    let mut indices = 0u64;
+    if a0 != a1 {
+        let palette = bc4_palette(a0, a1);
+        for (i, v) in values.iter().enumerate() {
+            let best = (0..8)
+                .min_by_key(|&k| (*v as i32 - palette[k] as i32).abs())
+                .unwrap();
+            indices |= (best as u64) << (3 * i);
+        }
+    }
+
+    let mut block = [0; 8];
+    block[0] = a0;
+    block[1] = a1;
+    block[2..8].copy_from_slice(&indices.to_le_bytes()[..6]);
+    block
+}
+
+fn decode_blocks(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    block_size: usize,
+    format: TextureFormat,
+) -> Vec<u8> {
+    let mut rgba = vec![0; width * height * 4];
+    let blocks_x = width.div_ceil(4);
+    for (i, block) in data.chunks_exact(block_size).enumerate() {
+        let (bx, by) = (i % blocks_x * 4, i / blocks_x * 4);
+        let texels = match format {
+            TextureFormat::Bc1 => decode_bc1(block, true),
+            TextureFormat::Bc2 => decode_bc2(block),
+            TextureFormat::Bc3 => decode_bc3(block),
+            TextureFormat::Bc4 => decode_bc4(block),
+            TextureFormat::Bc5 => decode_bc5(block),
+            TextureFormat::Bc7 => decode_bc7(block),
+            _ => unreachable!(),
+        };
+        // blocks at the right and bottom edge may be cut off
+        for (j, texel) in texels.iter().enumerate() {
+            let (x, y) = (bx + j % 4, by + j / 4);
+            if x < width && y < height {
+                let dst = (y * width + x) * 4;
+                rgba[dst..dst + 4].copy_from_slice(texel);
+            }
+        }
+    }
+    rgba
+}
+
+fn decode_uncompressed(data: &[u8], format: TextureFormat) -> Vec<u8> {
+    match format {
+        TextureFormat::Rgba8 => data.to_vec(),
+        TextureFormat::Bgra8 => data
+            .chunks_exact(4)
+            .flat_map(|p| [p[2], p[1], p[0], p[3]])
+            .collect(),
+        TextureFormat::Bgrx8 => data
+            .chunks_exact(4)
+            .flat_map(|p| [p[2], p[1], p[0], 255])
+            .collect(),
+        TextureFormat::Masked {
+            bytes_per_pixel,
+            masks,
+        } => data
+            .chunks_exact(bytes_per_pixel as usize)
+            .flat_map(|p| {
+                let mut raw = [0; 4];
+                raw[..p.len()].copy_from_slice(p);
+                let v = u32::from_le_bytes(raw);
+                let channel = |mask: u32, default: u8| {
+                    if mask == 0 {
+                        return default;
+                    }
+                    let max = mask >> mask.trailing_zeros();
+                    (((v & mask) >> mask.trailing_zeros()) * 255 / max) as u8
+                };
+                [
+                    channel(masks[0], 0),
+                    channel(masks[1], 0),
+                    channel(masks[2], 0),
+                    channel(masks[3], 255),
+                ]
+            })
+            .collect(),
+        _ => unreachable!(),
+    }
+}
+
+type Texels = [[u8; 4]; 16];
+
+fn rgb565(c: u16) -> [u8; 3] {
+    let r = ((c >> 11) & 0x1f) as u8;
+    let g = ((c >> 5) & 0x3f) as u8;
+    let b = (c & 0x1f) as u8;
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}
+
+/// `bc1_alpha == false` forces the four colour mode used by the colour block of BC2 and BC3.
+fn bc1_palette(c0: u16, c1: u16, bc1_alpha: bool) -> [[u8; 4]; 4] {
+    let (e0, e1) = (rgb565(c0), rgb565(c1));
+
+    let mut palette = [[0, 0, 0, 255]; 4];
+    for k in 0..3 {
+        let (a, b) = (e0[k] as u32, e1[k] as u32);
+        palette[0][k] = a as u8;
+        palette[1][k] = b as u8;
+        if c0 > c1 || !bc1_alpha {
+            palette[2][k] = ((2 * a + b) / 3) as u8;
+            palette[3][k] = ((a + 2 * b) / 3) as u8;
+        } else {
+            palette[2][k] = ((a + b) / 2) as u8;
+        }
+    }
+    if c0 <= c1 && bc1_alpha {
+        palette[3] = [0, 0, 0, 0];
+    }
+    palette
+}
+
+fn decode_bc1(block: &[u8], bc1_alpha: bool) -> Texels {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let palette = bc1_palette(c0, c1, bc1_alpha);
+
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    let mut texels = [[0; 4]; 16];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = palette[(indices >> (2 * i) & 0x3) as usize];
+    }
+    texels
+}
+
+fn bc4_palette(a0: u8, a1: u8) -> [u8; 8] {
+    let (a0, a1) = (a0 as u32, a1 as u32);
+    let mut palette = [0u8; 8];
+    palette[0] = a0 as u8;
+    palette[1] = a1 as u8;
+    if a0 > a1 {
+        for i in 1..7 {
+            palette[i + 1] = (((7 - i as u32) * a0 + i as u32 * a1) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = (((5 - i as u32) * a0 + i as u32 * a1) / 5) as u8;
+        }
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+    palette
+}
+
+/// Single channel block of BC3 alpha, BC4 and BC5.
+fn decode_bc4_channel(block: &[u8]) -> [u8; 16] {
+    let palette = bc4_palette(block[0], block[1]);
+
+    let mut raw = [0; 8];
+    raw[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(raw);
+    let mut values = [0; 16];
+    for (i, v) in values.iter_mut().enumerate() {
+        *v = palette[(indices >> (3 * i) & 0x7) as usize];
+    }
+    values
+}
+
+fn decode_bc2(block: &[u8]) -> Texels {
+    let mut texels = decode_bc1(&block[8..], false);
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let a = (block[i / 2] >> (4 * (i % 2))) & 0xf;
+        texel[3] = a * 17;
+    }
+    texels
+}
+
+fn decode_bc3(block: &[u8]) -> Texels {
+    let mut texels = decode_bc1(&block[8..], false);
+    for (texel, a) in texels.iter_mut().zip(decode_bc4_channel(&block[..8])) {
+        texel[3] = a;
+    }
+    texels
+}
+
+fn decode_bc4(block: &[u8]) -> Texels {
+    decode_bc4_channel(block).map(|r| [r, r, r, 255])
+}
+
+/// The two channels end up in red and green, blue is 0.
+fn decode_bc5(block: &[u8]) -> Texels {
+    let r = decode_bc4_channel(&block[..8]);
+    let g = decode_bc4_channel(&block[8..]);
+    let mut texels = [[0, 0, 0, 255]; 16];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        texel[0] = r[i];
+        texel[1] = g[i];
+    }
+    texels
+}
+
+struct BitReader {
+    bits: u128,
+}
+
+impl BitReader {
+    fn read(&mut self, count: u32) -> u8 {
+        let v = (self.bits & ((1 << count) - 1)) as u8;
+        self.bits >>= count;
+        v
+    }
+}
+
+/// Subset count, partition bits, rotation bits, index selection bits, colour bits, alpha bits,
+/// endpoint p-bits, shared p-bits, index bits, secondary index bits.
+const BC7_MODES: [[u32; 10]; 8] = [
+    [3, 4, 0, 0, 4, 0, 1, 0, 3, 0],
+    [2, 6, 0, 0, 6, 0, 0, 1, 3, 0],
+    [3, 6, 0, 0, 5, 0, 0, 0, 2, 0],
+    [2, 6, 0, 0, 7, 0, 1, 0, 2, 0],
+    [1, 0, 2, 1, 5, 6, 0, 0, 2, 3],
+    [1, 0, 2, 0, 7, 8, 0, 0, 2, 2],
+    [1, 0, 0, 0, 7, 7, 1, 0, 4, 0],
+    [2, 6, 0, 0, 5, 5, 1, 0, 2, 0],
+];
+
+const BC7_WEIGHTS2: [u32; 4] = [0, 21, 43, 64];
+const BC7_WEIGHTS3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const BC7_WEIGHTS4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Subset of every texel, bit i (2 bits per texel for three subsets).
+const PARTITION2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80, 0xc800, 0xffec, 0xfe80, 0xe800,
+    0xffe8, 0xff00, 0xfff0, 0xf000, 0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c, 0xaaaa, 0xf0f0, 0x5a5a, 0x33cc,
+    0x3c3c, 0x55aa, 0x9696, 0xa55a, 0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c, 0x9336, 0x9cc6, 0x817e, 0xe718,
+    0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+const PARTITION3: [u32; 64] = [
+    0xaa685050, 0x6a5a5040, 0x5a5a4200, 0x5450a0a8, 0xa5a50000, 0xa0a05050, 0x5555a0a0, 0x5a5a5050,
+    0xaa550000, 0xaa555500, 0xaaaa5500, 0x90909090, 0x94949494, 0xa4a4a4a4, 0xa9a59450, 0x2a0a4250,
+    0xa5945040, 0x0a425054, 0xa5a5a500, 0x55a0a0a0, 0xa8a85454, 0x6a6a4040, 0xa4a45000, 0x1a1a0500,
+    0x0050a4a4, 0xaaa59090, 0x14696914, 0x69691400, 0xa08585a0, 0xaa821414, 0x50a4a450, 0x6a5a0200,
+    0xa9a58000, 0x5090a0a8, 0xa8a09050, 0x24242424, 0x00aa5500, 0x24924924, 0x24499224, 0x50a50a50,
+    0x500aa550, 0xaaaa4444, 0x66660000, 0xa5a0a5a0, 0x50a050a0, 0x69286928, 0x44aaaa44, 0x66666600,
+    0xaa444444, 0x54a854a8, 0x95809580, 0x96969600, 0xa85454a8, 0x80959580, 0xaa141414, 0x96960000,
+    0xaaaa1414, 0xa05050a0, 0xa0a5a5a0, 0x96000000, 0x40804080, 0xa9a8a9a8, 0xaaaaaa44, 0x2a4a5254,
+];
+
+/// Texel whose index is stored with one bit less: second subset of two.
+const ANCHOR2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Second subset of three.
+const ANCHOR3_1: [u8; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5,
+    15, 15, 8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8, 5, 10, 5,
+    10, 8, 13, 15, 12, 3, 3,
+];
+
+/// Third subset of three.
+const ANCHOR3_2: [u8; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6,
+    10, 15, 15, 10, 8, 15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+/// Expands an endpoint of `bits` bits to 8 bits by replicating the high bits.
+fn bc7_unquantize(v: u8, bits: u32) -> u8 {
+    let v = (v as u32) << (8 - bits);
+    (v | v >> bits) as u8
+}
+
+fn bc7_interpolate(e0: u8, e1: u8, index: u8, index_bits: u32) -> u8 {
+    let w = match index_bits {
+        2 => BC7_WEIGHTS2[index as usize],
+        3 => BC7_WEIGHTS3[index as usize],
+        _ => BC7_WEIGHTS4[index as usize],
+    };
+    (((64 - w) * e0 as u32 + w * e1 as u32 + 32) >> 6) as u8
+}
+
+fn decode_bc7(block: &[u8]) -> Texels {
+    let mut bits = BitReader {
+        bits: u128::from_le_bytes(block.try_into().unwrap()),
+    };
+    let mode = block[0].trailing_zeros() as usize;
+    if mode >= 8 {
+        // reserved mode
+        return [[0; 4]; 16];
+    }
+    bits.read(mode as u32 + 1);
+    let [subsets, pb, rb, isb, cb, ab, epb, spb, ib, ib2] = BC7_MODES[mode];
+    let subsets = subsets as usize;
+
+    let partition = bits.read(pb) as usize;
+    let rotation = bits.read(rb);
+    let index_selection = bits.read(isb);
+
+    // endpoints[subset * 2 + endpoint][channel]
+    let mut endpoints = [[0u8; 4]; 6];
+    for channel in 0..3 {
+        for e in endpoints.iter_mut().take(2 * subsets) {
+            e[channel] = bits.read(cb);
+        }
+    }
+    for e in endpoints.iter_mut().take(2 * subsets) {
+        e[3] = if ab > 0 { bits.read(ab) } else { 255 };
+    }
+
+    let (mut cb, mut ab) = (cb, ab);
+    if epb > 0 || spb > 0 {
+        let mut p_bits = [0u8; 6];
+        if epb > 0 {
+            for p in p_bits.iter_mut().take(2 * subsets) {
+                *p = bits.read(1);
+            }
+        } else {
+            for s in 0..subsets {
+                let p = bits.read(1);
+                p_bits[2 * s] = p;
+                p_bits[2 * s + 1] = p;
+            }
+        }
+        for (e, p) in endpoints.iter_mut().zip(p_bits).take(2 * subsets) {
+            for c in e.iter_mut().take(3) {
+                *c = (*c << 1) | p;
+            }
+            if ab > 0 {
+                e[3] = (e[3] << 1) | p;
+            }
+        }
+        cb += 1;
+        if ab > 0 {
+            ab += 1;
+        }
+    }
+    for e in endpoints.iter_mut().take(2 * subsets) {
+        for c in e.iter_mut().take(3) {
+            *c = bc7_unquantize(*c, cb);
+        }
+        if ab > 0 {
+            e[3] = bc7_unquantize(e[3], ab);
+        }
+    }
+
+    let subset_of = |texel: usize| -> usize {
+        match subsets {
+            2 => (PARTITION2[partition] >> texel & 1) as usize,
+            3 => (PARTITION3[partition] >> (2 * texel) & 3) as usize,
+            _ => 0,
+        }
+    };
+    let is_anchor = |texel: usize| -> bool {
+        texel == 0
+            || match subsets {
+                2 => texel == ANCHOR2[partition] as usize,
+                3 => {
+                    texel == ANCHOR3_1[partition] as usize || texel == ANCHOR3_2[partition] as usize
+                }
+                _ => false,
+            }
+    };
+
+    let mut indices = [0u8; 16];
+    for (texel, index) in indices.iter_mut().enumerate() {
+        *index = bits.read(if is_anchor(texel) { ib - 1 } else { ib });
+    }
+    let mut indices2 = [0u8; 16];
+    if ib2 > 0 {
+        for (texel, index) in indices2.iter_mut().enumerate() {
+            *index = bits.read(if texel == 0 { ib2 - 1 } else { ib2 });
+        }
+    }
+
+    let mut texels = [[0; 4]; 16];
+    for (texel, out) in texels.iter_mut().enumerate() {
+        let s = subset_of(texel);
+        let (e0, e1) = (endpoints[2 * s], endpoints[2 * s + 1]);
+        let (color_index, color_bits, alpha_index, alpha_bits) = if ib2 == 0 {
+            (indices[texel], ib, indices[texel], ib)
+        } else if index_selection == 0 {
+            (indices[texel], ib, indices2[texel], ib2)
+        } else {
+            (indices2[texel], ib2, indices[texel], ib)
+        };
+        for channel in 0..3 {
+            out[channel] = bc7_interpolate(e0[channel], e1[channel], color_index, color_bits);
+        }
+        out[3] = bc7_interpolate(e0[3], e1[3], alpha_index, alpha_bits);
+
+        match rotation {
+            1 => out.swap(0, 3),
+            2 => out.swap(1, 3),
+            3 => out.swap(2, 3),
+            _ => {}
+        }
+    }
+    texels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bc1_block() {
+        // red and blue endpoints, texel i uses index i % 4
+        let block = [0x00, 0xf8, 0x1f, 0x00, 0xe4, 0xe4, 0xe4, 0xe4];
+        let texels = decode_bc1(&block, true);
+        assert_eq!(texels[0], [255, 0, 0, 255]);
+        assert_eq!(texels[1], [0, 0, 255, 255]);
+        assert_eq!(texels[2], [170, 0, 85, 255]);
+        assert_eq!(texels[3], [85, 0, 170, 255]);
+    }
+
+    #[test]
+    fn encode_round_trip() {
+        let (width, height) = (13, 6);
+        let mut rgba = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let t = x as u8;
+                rgba.extend([t * 14, 255 - t * 9, 128, 255 - y as u8 * 20]);
+            }
+        }
+        let image = DdsImage::from_rgba(width, height, rgba.clone());
+        let dds = image.to_dds().unwrap();
+
+        let header: DdsHeader = io::Cursor::new(&dds).read_le().unwrap();
+        assert_eq!(header.mip_map_count, 4);
+        assert_eq!(header.pixel_format.four_cc, *b"DXT5");
+        // 13x6: 4x2 blocks, 6x3: 2x1, 3x1: 1x1, 1x1: 1x1
+        assert_eq!(dds.len(), 128 + (8 + 2 + 1 + 1) * 16);
+
+        let decoded = DdsImage::try_from_bytes(&dds).unwrap();
+        assert_eq!(decoded.format, TextureFormat::Bc3);
+        for (a, b) in decoded.rgba.iter().zip(rgba.iter()) {
+            assert!((*a as i32 - *b as i32).abs() <= 8, "{} {}", a, b);
+        }
+    }
+
+    #[test]
+    fn bc7_mode6_block() {
+        // mode 6 with both endpoints white and opaque, all indices 0
+        let mut bits: u128 = 1 << 6;
+        let mut offset = 7;
+        for _ in 0..8 {
+            bits |= 0x7f << offset;
+            offset += 7;
+        }
+        bits |= 0b11 << offset;
+        let texels = decode_bc7(&bits.to_le_bytes());
+        assert!(texels.iter().all(|t| *t == [255; 4]));
+    }
+}
@@ -1,12 +1,17 @@
-use crate::{rdm_data_main::MeshInfo, rdm_material::RdMaterial, vertex::*, RdJoint, RdModell};
+use crate::{
+    dds::DdsImage,
+    rdm_anim::{FrameCollection, RdAnim},
+    rdm_data_main::MeshInfo,
+    vertex::*,
+    RdJoint, RdModell,
+};
 use gltf::{json, json::validation::Checked::Valid, mesh::Semantic};
 use std::{
     borrow::Cow,
     collections::{BTreeMap, HashMap},
     convert::TryInto,
-    env,
-    fs::{self, File, OpenOptions},
-    io::{self, Read, Write},
+    fs::{self, OpenOptions},
+    io::{self, Write},
     path::PathBuf,
     str::FromStr,
 };
@@ -38,7 +43,7 @@ struct RdGltfBuilder {
     rdm: RdModell,
     obj: RdGltf, // private
     skin: Option<json::Skin>,
-    anim_node: Option<json::Animation>,
+    anim_nodes: Vec<json::Animation>,
     material_idx: Option<Vec<u32>>,
     material_vec: Vec<json::Material>,
     texture_vec: Vec<json::Texture>,
@@ -58,7 +63,7 @@ impl RdGltfBuilder {
             obj: RdGltf::new(),
             skin: None,
             idx: None,
-            anim_node: None,
+            anim_nodes: vec![],
             material_idx: None,
             material_vec: vec![],
             texture_vec: vec![],
@@ -68,15 +73,41 @@ impl RdGltfBuilder {
     }
 
     pub fn put_rdm_anim(&mut self) {
+        for anim in self.rdm.anims.clone() {
+            self.put_anim(anim);
+        }
+    }
+
+    fn put_anim(&mut self, anim: RdAnim) {
         // TODO: must not circumvent PushBufferResult
         let buffv_idx = self.buffers.len() as u32;
         let mut bv_idx = self.buffer_views.len() as u32;
         let mut acc_idx = self.accessors.len() as u32;
 
-        let anim = self.rdm.anim.clone().unwrap();
-        let anim_vec = anim.anim_vec.clone();
+        let p = self.rdm.joints.clone().unwrap();
+        let mut modell_nodes = HashMap::new();
+
+        for (i, joint) in p.iter().enumerate() {
+            modell_nodes.insert(joint.name.clone(), i);
+        }
+
+        // tracks without a joint in the skeleton are dropped
+        let anim_vec: Vec<(u32, &FrameCollection)> = anim
+            .anim_vec
+            .iter()
+            .filter_map(|janim| match modell_nodes.get(&janim.name) {
+                Some(idx) => Some((*idx as u32, janim)),
+                None => {
+                    warn!(
+                        "Skipping track {:?} of {}: no such joint in base model",
+                        &janim.name, anim.name
+                    );
+                    None
+                }
+            })
+            .collect();
 
-        let size: usize = anim_vec.iter().map(|f| f.frames.len()).sum();
+        let size: usize = anim_vec.iter().map(|(_, f)| f.frames.len()).sum();
 
         let rot_size = size * 16;
         let trans_size = size * 12;
@@ -97,37 +128,7 @@ impl RdGltfBuilder {
         let mut sampler_vec = Vec::new();
         let mut chanel_vec = Vec::new();
 
-        let p = self.rdm.joints.clone().unwrap();
-        let mut modell_nodes = HashMap::new();
-
-        for (i, joint) in p.iter().enumerate() {
-            modell_nodes.insert(joint.name.clone(), i);
-        }
-        for (_, janim) in anim_vec.iter().enumerate() {
-            let target_node_idx = match modell_nodes.get(&janim.name) {
-                Some(idx) => *idx as u32,
-                None => {
-                    warn!(
-                        "Could not find animation target {:?} in base model {:?}",
-                        &janim.name, p
-                    );
-                    //TODO: proper fix for unused animation targets.
-                    for _ in 0..janim.frames.len() {
-                        rot_anim_buf.put_u32_le(0xDEAD_BEEF);
-                        rot_anim_buf.put_u32_le(0xDEAD_BEEF);
-                        rot_anim_buf.put_u32_le(0xDEAD_BEEF);
-                        rot_anim_buf.put_u32_le(0xDEAD_BEEF);
-
-                        trans_anim_buf.put_u32_le(0xDEAD_BEEF);
-                        trans_anim_buf.put_u32_le(0xDEAD_BEEF);
-                        trans_anim_buf.put_u32_le(0xDEAD_BEEF);
-
-                        t_anim_buf.put_u32_le(0xDEAD_BEEF);
-                    }
-                    continue;
-                }
-            };
-
+        for (target_node_idx, janim) in anim_vec {
             let count = janim.frames.len();
 
             let rot_start = rot_anim_buf.len();
@@ -283,7 +284,7 @@ impl RdGltfBuilder {
             let rot_chanel = json::animation::Channel {
                 sampler: json::Index::new(rot_sampler_chanel),
                 target: json::animation::Target {
-                    node: json::Index::new(target_node_idx as u32),
+                    node: json::Index::new(target_node_idx),
                     path: Valid(json::animation::Property::Rotation),
                     extensions: None,
                     extras: None,
@@ -295,7 +296,7 @@ impl RdGltfBuilder {
             let trans_chanel = json::animation::Channel {
                 sampler: json::Index::new(trans_sampler_chanel),
                 target: json::animation::Target {
-                    node: json::Index::new(target_node_idx as u32),
+                    node: json::Index::new(target_node_idx),
                     path: Valid(json::animation::Property::Translation),
                     extensions: None,
                     extras: None,
@@ -314,6 +315,14 @@ impl RdGltfBuilder {
             trans_sampler_chanel += 2;
         }
 
+        if chanel_vec.is_empty() {
+            warn!(
+                "Skipping animation {}: no track targets the skeleton",
+                anim.name
+            );
+            return;
+        }
+
         let anim_node = json::animation::Animation {
             name: Some(anim.name),
             samplers: sampler_vec,
@@ -347,7 +356,7 @@ impl RdGltfBuilder {
 
         self.accessors.append(&mut acc_vec);
 
-        self.anim_node = Some(anim_node);
+        self.anim_nodes.push(anim_node);
     }
 
     fn put_joint_weight(&mut self, normalise: bool) {
@@ -637,15 +646,23 @@ impl RdGltfBuilder {
     }
 
     fn put_tex(&mut self) {
-        let mut tbuffers = Vec::new();
-        if let Some(iter) = self.rdm.vertex.iter::<T2h, T2f>(0) {
-            let mut buff = BytesMut::with_capacity(2 * 4 * self.rdm.vertex.vertex_count as usize);
-
-            for t2h in iter {
-                buff.put_f32_le(t2h.data[0]);
-                buff.put_f32_le(t2h.data[1]);
+        let n = self
+            .rdm
+            .vertex
+            .find_component_offsets(UniqueIdentifier::Texcoord)
+            .count();
+        let mut tbuffers = Vec::with_capacity(n);
+        for set in 0..n {
+            if let Some(iter) = self.rdm.vertex.iter::<T2h, T2f>(set) {
+                let mut buff =
+                    BytesMut::with_capacity(2 * 4 * self.rdm.vertex.vertex_count as usize);
+
+                for t2h in iter {
+                    buff.put_f32_le(t2h.data[0]);
+                    buff.put_f32_le(t2h.data[1]);
+                }
+                tbuffers.push(BufferContainer::Bytes(buff.freeze()));
             }
-            tbuffers.push(BufferContainer::Bytes(buff.freeze()));
         }
         for (i, b) in tbuffers.into_iter().enumerate() {
             self.put_attr(
@@ -705,7 +722,7 @@ impl RdGltfBuilder {
 
         let mut material_idx_vec = Vec::with_capacity(material_len);
         assert!(self.material_vec.is_empty());
-        for itex in texture_info_descriptors {
+        for (i, itex) in texture_info_descriptors.into_iter().enumerate() {
             let pbr = json::material::PbrMetallicRoughness {
                 base_color_texture: itex,
                 ..Default::default()
@@ -715,6 +732,7 @@ impl RdGltfBuilder {
                 alpha_cutoff: None,
                 alpha_mode: Valid(json::material::AlphaMode::Opaque),
                 pbr_metallic_roughness: pbr,
+                name: self.rdm.materials.get(i).map(|m| m.name.clone()),
                 ..Default::default()
             };
 
@@ -728,20 +746,13 @@ impl RdGltfBuilder {
 
     fn run_dds(&mut self, embed_image_buffer: bool) {
         if let Some(mats) = self.rdm.mat.as_ref() {
-            let dir = env::temp_dir();
-            mats.run_dds_converter(&dir);
-
-            if embed_image_buffer {
-                let tmp_dir = env::temp_dir();
-                for e in self.image_vec.iter_mut() {
-                    let src_dds_file = tmp_dir.join(e.uri.as_ref().unwrap());
-                    let mut f = File::open(src_dds_file).unwrap();
-                    // prealloc 10 mebibytes for the png
-                    let mut buffer = Vec::with_capacity(10 * 1024 * 1024);
-
-                    // read the whole file
-                    f.read_to_end(&mut buffer).unwrap();
+            for (e, dds_path) in self.image_vec.iter_mut().zip(mats) {
+                info!("decoding {:?}", dds_path);
+                let buffer = DdsImage::try_from_path(dds_path)
+                    .and_then(|dds| dds.to_png())
+                    .unwrap_or_else(|err| panic!("{}: {}", dds_path.display(), err));
 
+                if embed_image_buffer {
                     e.uri = None;
                     e.mime_type = Some(json::image::MimeType("image/png".to_string()));
                     let buffer_view_idx = RdGltfBuilder::put_buffer_and_view(
@@ -752,6 +763,8 @@ impl RdGltfBuilder {
                         None,
                     );
                     e.buffer_view = Some(json::Index::new(buffer_view_idx));
+                } else {
+                    self.obj.images.push((e.uri.clone().unwrap(), buffer));
                 }
             }
         }
@@ -867,20 +880,39 @@ impl RdGltfBuilder {
         let mut buff = BytesMut::with_capacity(3 * 4 * self.rdm.vertex.vertex_count as usize);
 
         if let Some(iter) = self.rdm.vertex.iter::<G4b, G3f>(0) {
-            for g3f in iter {
+            let normals: Vec<N3f> = self
+                .rdm
+                .vertex
+                .iter::<N4b, N3f>(0)
+                .map(|i| i.collect())
+                .unwrap_or_default();
+            let bitangents: Vec<B3f> = self
+                .rdm
+                .vertex
+                .iter::<B4b, B3f>(0)
+                .map(|i| i.collect())
+                .unwrap_or_default();
+
+            for (i, g3f) in iter.enumerate() {
                 let t = g3f.normalise().data;
                 buff.put_f32_le(t[0]);
                 buff.put_f32_le(t[1]);
                 buff.put_f32_le(t[2]);
 
-                buff.put_f32_le(1.0);
-                /* TODO is this right ?
-                if relative_eq!(g4b.data[3], 1.0f32) {
-                    buff.put_f32_le(1.0);
-                } else {
-                    buff.put_f32_le(-1.0);
-                }
-                */
+                // handedness: the glTF bitangent is cross(normal, tangent) * w
+                let w = match (normals.get(i), bitangents.get(i)) {
+                    (Some(n), Some(b)) => {
+                        let n = Vector3::from(n.data);
+                        let b = Vector3::from(b.data);
+                        if n.cross(&Vector3::from(t)).dot(&b) < 0.0 {
+                            -1.0
+                        } else {
+                            1.0
+                        }
+                    }
+                    _ => 1.0,
+                };
+                buff.put_f32_le(w);
             }
         }
         if !buff.is_empty() {
@@ -898,6 +930,58 @@ impl RdGltfBuilder {
         }
     }
 
+    /// Raw B4b data as custom `_BITANGENT` attribute. `TANGENT.w` only keeps the handedness.
+    /// B3f bitangents are not exported, the import computes them from the normal and tangent.
+    fn put_bitangent(&mut self) {
+        let mut buff = BytesMut::with_capacity(4 * self.rdm.vertex.vertex_count as usize);
+        let packed =
+            self.rdm.vertex.identifiers.iter().any(|x| {
+                x.uniq == UniqueIdentifier::Bitangent && x.unit_size == IdentifierSize::U32
+            });
+        if !packed {
+            warn!("No B4b bitangent in the rdm, the raw bitangent is not exported.");
+        } else if let Some(iter) = self.rdm.vertex.iter::<B4b, B4b>(0) {
+            for b4b in iter {
+                buff.put_slice(&b4b.data);
+            }
+        }
+        if !buff.is_empty() {
+            self.put_attr(
+                BufferContainer::Bytes(buff.freeze()),
+                json::accessor::Type::Vec4,
+                json::accessor::ComponentType::U8,
+                None,
+                Some(json::mesh::Semantic::Extras(BITANGENT_ATTRIBUTE.into())),
+                None,
+                None,
+                None,
+            );
+        }
+    }
+
+    fn put_color(&mut self) {
+        let mut buff = BytesMut::with_capacity(4 * self.rdm.vertex.vertex_count as usize);
+        if let Some(iter) = self.rdm.vertex.iter::<C4c, C4c>(0) {
+            for c4c in iter {
+                buff.put_slice(&c4c.data);
+            }
+        }
+        if !buff.is_empty() {
+            let acc = self.put_attr(
+                BufferContainer::Bytes(buff.freeze()),
+                json::accessor::Type::Vec4,
+                json::accessor::ComponentType::U8,
+                None,
+                Some(json::mesh::Semantic::Colors(0)),
+                None,
+                None,
+                None,
+            );
+            // COLOR_0 with unsigned byte components must be normalized
+            self.accessors[acc as usize].normalized = true;
+        }
+    }
+
     fn put_idx(&mut self) {
         let mut bytes = Vec::with_capacity(self.rdm.mesh_info.len());
         let mut accessor_idx_meshes = Vec::with_capacity(self.rdm.mesh_info.len());
@@ -925,7 +1009,7 @@ impl RdGltfBuilder {
     }
 
     pub fn build(mut self) -> RdGltf {
-        let animation = self.anim_node.map_or(Default::default(), |n| vec![n]);
+        let animation = self.anim_nodes;
 
         // put_material must already have been run otherwise this panics!
         let mats = self.material_idx.unwrap();
@@ -1046,10 +1130,26 @@ impl RdGltfBuilder {
     }
 }
 
+/// Name of the custom attribute holding the raw B4b data, written as `_BITANGENT`.
+pub(crate) const BITANGENT_ATTRIBUTE: &str = "BITANGENT";
+
+/// Optional settings of the rdm to glTF export.
+#[derive(Debug, Clone, Default)]
+pub struct GltfExportOptions {
+    /// Also export the raw B4b stream as `_BITANGENT`, so that glTF to rdm restores it exactly.
+    pub raw_bitangent: bool,
+}
+
 impl From<RdModell> for RdGltfBuilder {
     fn from(rdm: RdModell) -> Self {
+        RdGltfBuilder::with_options(rdm, &GltfExportOptions::default())
+    }
+}
+
+impl RdGltfBuilder {
+    fn with_options(rdm: RdModell, options: &GltfExportOptions) -> Self {
         let has_skin = rdm.has_skin();
-        let has_anim = rdm.anim.is_some();
+        let has_anim = !rdm.anims.is_empty();
 
         let mut b = RdGltfBuilder::new(rdm);
 
@@ -1061,6 +1161,10 @@ impl From<RdModell> for RdGltfBuilder {
 
         b.put_normal();
         b.put_tangent();
+        if options.raw_bitangent {
+            b.put_bitangent();
+        }
+        b.put_color();
 
         if has_skin {
             b.put_joint_nodes(JointOption::ResolveParentNode);
@@ -1076,8 +1180,17 @@ impl From<RdModell> for RdGltfBuilder {
 }
 
 pub fn build(rdm: RdModell, dir: Option<PathBuf>, create_new: bool, config: GltfExportFormat) {
-    let mat_opt = rdm.mat.clone();
-    let mut b = RdGltfBuilder::from(rdm);
+    build_with_options(rdm, dir, create_new, config, &GltfExportOptions::default());
+}
+
+pub fn build_with_options(
+    rdm: RdModell,
+    dir: Option<PathBuf>,
+    create_new: bool,
+    config: GltfExportFormat,
+    options: &GltfExportOptions,
+) {
+    let mut b = RdGltfBuilder::with_options(rdm, options);
     b.run_dds(config == GltfExportFormat::Glb);
     if config == GltfExportFormat::Glb || config == GltfExportFormat::GltfSeparateMinimise {
         b.merge_buffers();
@@ -1089,11 +1202,13 @@ pub fn build(rdm: RdModell, dir: Option<PathBuf>, create_new: bool, config: Gltf
     let p = b.build();
     info!("gltf build end");
     info!("write_gltf");
-    p.write_gltf(dir, mat_opt, create_new, config);
+    p.write_gltf(dir, create_new, config);
 }
 
 struct RdGltf {
     buffers: Vec<BufferContainer>,
+    /// Decoded textures (uri, png) that are written next to the gltf file.
+    images: Vec<(String, Vec<u8>)>,
     root: Option<json::Root>,
 }
 enum BufferContainer {
@@ -1110,7 +1225,7 @@ impl BufferContainer {
     fn get_padded_added(&self) -> usize {
         let real_len = self.get_bytes_len_real();
         assert_ne!(real_len, 0);
-        if real_len % 4 == 0 {
+        if real_len.is_multiple_of(4) {
             0
         } else {
             4 - (real_len % 4)
@@ -1170,17 +1285,12 @@ impl RdGltf {
     fn new() -> Self {
         RdGltf {
             buffers: vec![],
+            images: vec![],
             root: None,
         }
     }
 
-    fn write_gltf(
-        self,
-        dir: Option<PathBuf>,
-        optmat: Option<RdMaterial>,
-        create_new: bool,
-        config: GltfExportFormat,
-    ) {
+    fn write_gltf(self, dir: Option<PathBuf>, create_new: bool, config: GltfExportFormat) {
         let mut file = dir.unwrap_or_else(|| {
             let f = PathBuf::from("gltf_out");
             let _ = fs::create_dir(&f);
@@ -1247,17 +1357,10 @@ impl RdGltf {
                     bin.to_writer(&mut writer).unwrap();
                 }
 
-                // copy converted png from tmp to dest
-                if let Some(mat) = optmat.as_ref() {
-                    let tmp_dir = env::temp_dir();
-                    for e in mat.into_iter() {
-                        let mut src = tmp_dir.join(e.file_stem().unwrap());
-                        src.set_extension("PNG");
-                        let mut dst_file = udir.join(e.file_stem().unwrap());
-                        dst_file.set_extension("PNG");
-                        debug!("copy: {:?} to {:?}", &src, &dst_file);
-                        fs::copy(src, &dst_file).unwrap();
-                    }
+                for (uri, png) in self.images.iter() {
+                    let dst_file = udir.join(uri);
+                    debug!("write_all {:?}", &dst_file);
+                    fs::write(&dst_file, png).expect("I/O error");
                 }
             }
         }
@@ -1,16 +1,21 @@
+use crate::dds::DdsImage;
+use crate::gltf_export::BITANGENT_ATTRIBUTE;
+pub use crate::gltf_reader_geometry::NormalMode;
+use crate::gltf_reader_geometry::{
+    generate_normals, generate_tangents, has_triangles, remap, triangle_list,
+};
 use crate::rdm_data_anim::Frame;
-use crate::rdm_data_main::MeshInfo;
+use crate::rdm_data_main::{MeshInfo, MESH_FILE_STEM};
+use crate::rdm_material::RdmMaterialInfo;
 use crate::vertex::*;
 use crate::RdModell;
-use crate::{gltf_reader_vertex::PutVertex, RdJoint};
+use crate::{gltf_reader_vertex::PutComponent, RdJoint};
 use crate::{vertex::TargetVertexFormat, Triangle};
 
-use gltf::animation::Channel;
+use gltf::animation::Interpolation;
 use gltf::Node;
 use nalgebra::*;
 
-use half::f16;
-
 use bytes::BytesMut;
 
 use crate::rdm_anim::*;
@@ -19,10 +24,11 @@ use gltf::animation::util::ReadOutputs::*;
 use crate::VertexFormat2;
 use std::collections::hash_map::DefaultHasher;
 use std::collections::HashSet;
+use std::fs::OpenOptions;
 use std::hash::{Hash, Hasher};
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::u16;
 use std::{
     collections::HashMap,
     convert::{TryFrom, TryInto},
@@ -46,10 +52,67 @@ impl FromStr for ResolveNodeName {
     }
 }
 
+/// Key spacing of imported animations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnimSampleRate {
+    /// Keys per second.
+    Fps(f32),
+    /// Number of keys over the whole time range.
+    Keys(usize),
+}
+
+/// Time range and key grid of imported animations.
+/// Joints without animation are always written on this grid, animated channels only with
+/// `resample`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimSampling {
+    pub rate: AnimSampleRate,
+    /// Start of the time range in seconds. Written animations start at 0.
+    pub start: Option<f32>,
+    /// End of the time range in seconds. Defaults to the last keyframe of the glTF animation.
+    pub end: Option<f32>,
+    /// Resample every channel onto the grid instead of keeping the glTF keyframes.
+    pub resample: bool,
+}
+
+impl Default for AnimSampling {
+    fn default() -> Self {
+        Self {
+            rate: AnimSampleRate::Keys(7),
+            start: None,
+            end: None,
+            resample: false,
+        }
+    }
+}
+
+impl AnimSampling {
+    fn grid(&self, start: f32, end: f32) -> Vec<f32> {
+        let intervals = match self.rate {
+            AnimSampleRate::Fps(fps) => ((end - start) * fps).round().max(1.0) as usize,
+            AnimSampleRate::Keys(keys) => keys.max(2) - 1,
+        };
+        let step = (end - start) / intervals as f32;
+        (0..=intervals).map(|i| start + i as f32 * step).collect()
+    }
+}
+
 pub struct ImportedGltf {
     gltf: gltf::Document,
     buffers: Vec<gltf::buffer::Data>,
+    images: Vec<gltf::image::Data>,
     pub name_setting: ResolveNodeName,
+    /// Import every mesh of the scene with its global node transform instead of the mesh at
+    /// `mesh_idx` only. When reading joints, skinned meshes must use the skin of the mesh at
+    /// `mesh_idx` and get its transform.
+    pub all_meshes: bool,
+    /// Normals computed for primitives without `NORMAL`.
+    pub normal_mode: NormalMode,
+    /// Smoothing angle threshold in degrees of `NormalMode::Smooth`.
+    pub normal_angle: f32,
+    /// The textures are written with `write_textures`: the rdm materials reference the written
+    /// `_diff_0.dds` instead of the glTF image.
+    pub textures: bool,
     mesh_idx: u32,
     mesh_node: u32,
 }
@@ -106,18 +169,157 @@ fn extract_translations(
     frames
 }
 
-fn read_animation_channel(buffers: &[gltf::buffer::Data], channel: Channel) -> Vec<Frame> {
-    let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
-    let time = reader.read_inputs().unwrap();
-    let output = reader.read_outputs().unwrap();
+fn same_timeline(a: &[Frame], b: &[Frame]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| relative_eq!(a.time, b.time))
+}
+
+/// Index of the keyframe segment containing `t` and the blend factor inside that segment.
+/// Times before the first or after the last keyframe are clamped.
+/// At a step (two keyframes with the same time) `left` selects the value before the step.
+fn keyframe_segment(frames: &[Frame], t: f32, left: bool) -> (usize, usize, f32) {
+    let next = if left {
+        frames.partition_point(|f| f.time < t)
+    } else {
+        frames.partition_point(|f| f.time <= t)
+    };
+    if next == 0 {
+        return (0, 0, 0.0);
+    }
+    if next == frames.len() {
+        return (next - 1, next - 1, 0.0);
+    }
+    let (t0, t1) = (frames[next - 1].time, frames[next].time);
+    (next - 1, next, (t - t0) / (t1 - t0))
+}
+
+fn sample_translation(frames: &[Frame], t: f32, left: bool) -> [f32; 3] {
+    let (i0, i1, f) = keyframe_segment(frames, t, left);
+    let a = Vector3::from(frames[i0].translation);
+    let b = Vector3::from(frames[i1].translation);
+    a.lerp(&b, f).into()
+}
+
+fn sample_rotation(frames: &[Frame], t: f32, left: bool) -> [f32; 4] {
+    let (i0, i1, f) = keyframe_segment(frames, t, left);
+    let quat = |r: [f32; 4]| UnitQuaternion::from_quaternion(Quaternion::from(Vector4::from(r)));
+    let a = quat(frames[i0].rotation);
+    let b = quat(frames[i1].rotation);
+    let r = a
+        .try_slerp(&b, f, f32::EPSILON)
+        .unwrap_or_else(|| a.nlerp(&b, f));
+    r.coords.into()
+}
+
+fn has_step(frames: &[Frame], t: f32) -> bool {
+    frames
+        .windows(2)
+        .any(|w| relative_eq!(w[0].time, t) && relative_eq!(w[1].time, t))
+}
+
+/// Samples a translation and a rotation channel at the sorted `times`.
+/// Steps in either channel are kept as two frames with the same time.
+fn sample_channels(translations: &[Frame], rotations: &[Frame], times: &[f32]) -> Vec<Frame> {
+    let mut frames = Vec::with_capacity(times.len());
+    for &t in times {
+        let sample = |left| Frame {
+            rotation: sample_rotation(rotations, t, left),
+            translation: sample_translation(translations, t, left),
+            time: t,
+        };
+        if has_step(translations, t) || has_step(rotations, t) {
+            frames.push(sample(true));
+        }
+        frames.push(sample(false));
+    }
+    frames
+}
+
+/// Merges a translation and a rotation channel with different keyframe times onto the union of
+/// both timelines. Translations are interpolated linearly and rotations with slerp.
+fn resample_channels(translations: &[Frame], rotations: &[Frame]) -> Vec<Frame> {
+    let mut times: Vec<f32> = translations
+        .iter()
+        .chain(rotations)
+        .map(|f| f.time)
+        .collect();
+    times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    times.dedup_by(|a, b| relative_eq!(*a, *b));
+    sample_channels(translations, rotations, &times)
+}
+
+/// An empty `selection` selects every animation, otherwise an entry has to match the
+/// animation name or index.
+fn anim_selected(index: usize, name: Option<&str>, selection: &[String]) -> bool {
+    selection.is_empty()
+        || selection
+            .iter()
+            .any(|s| Some(s.as_str()) == name || s.parse() == Ok(index))
+}
+
+fn file_name_safe(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect()
+}
 
-    match output {
-        Rotations(rot) => {
-            let rot_iter = rot.into_f32();
-            extract_rotations(time, Default::default(), rot_iter)
+/// Number of linear keys a CUBICSPLINE segment is sampled into.
+const CUBICSPLINE_SAMPLES: usize = 8;
+
+/// Converts the keys of a sampler into keys that can be interpolated linearly.
+/// STEP keys are duplicated at the time of the next key and CUBICSPLINE segments are sampled.
+/// `None` if a CUBICSPLINE sampler does not have three values per key.
+fn linear_keys<const N: usize>(
+    interpolation: Interpolation,
+    time: impl Iterator<Item = f32>,
+    values: impl Iterator<Item = [f32; N]>,
+) -> Option<Vec<(f32, [f32; N])>> {
+    let time: Vec<f32> = time.collect();
+    let values: Vec<[f32; N]> = values.collect();
+    match interpolation {
+        Interpolation::Linear => Some(time.into_iter().zip(values).collect()),
+        Interpolation::Step => {
+            let mut keys = Vec::with_capacity(2 * time.len());
+            for (i, (t, v)) in time.iter().zip(&values).enumerate() {
+                if i > 0 {
+                    keys.push((*t, values[i - 1]));
+                }
+                keys.push((*t, *v));
+            }
+            Some(keys)
+        }
+        Interpolation::CubicSpline => {
+            // values are stored as (in-tangent, value, out-tangent) triplets
+            if values.len() != 3 * time.len() {
+                return None;
+            }
+            let mut keys = Vec::with_capacity(CUBICSPLINE_SAMPLES * time.len());
+            for k in 0..time.len() {
+                let v0 = values[3 * k + 1];
+                if k + 1 == time.len() {
+                    keys.push((time[k], v0));
+                    break;
+                }
+                let b0 = values[3 * k + 2];
+                let a1 = values[3 * (k + 1)];
+                let v1 = values[3 * (k + 1) + 1];
+                let td = time[k + 1] - time[k];
+                for j in 0..CUBICSPLINE_SAMPLES {
+                    let s = j as f32 / CUBICSPLINE_SAMPLES as f32;
+                    let (s2, s3) = (s * s, s * s * s);
+                    let p: [f32; N] = std::array::from_fn(|c| {
+                        (2.0 * s3 - 3.0 * s2 + 1.0) * v0[c]
+                            + td * (s3 - 2.0 * s2 + s) * b0[c]
+                            + (-2.0 * s3 + 3.0 * s2) * v1[c]
+                            + td * (s3 - s2) * a1[c]
+                    });
+                    keys.push((time[k] + s * td, p));
+                }
+            }
+            Some(keys)
         }
-        Translations(trans) => extract_translations(time, Default::default(), trans),
-        _ => unreachable!(),
     }
 }
 
@@ -128,11 +330,16 @@ impl<'a> ImportedGltf {
         joint_name_src: ResolveNodeName,
     ) -> Result<ImportedGltf, gltf::Error> {
         info!("gltf::import start!");
-        let (gltf, buffers, _) = gltf::import(f_path)?;
+        let (gltf, buffers, images) = gltf::import(f_path)?;
         let mut res = Self {
             gltf,
             buffers,
+            images,
             name_setting: joint_name_src,
+            all_meshes: false,
+            normal_mode: NormalMode::Smooth,
+            normal_angle: 60.0,
+            textures: false,
             mesh_idx: 0,
             mesh_node: 0,
         };
@@ -158,6 +365,40 @@ impl<'a> ImportedGltf {
         self.mesh_node = mesh_instantiating_node as u32;
     }
 
+    /// Meshes to import and the node instantiating each of them. In `all_meshes` mode the scene
+    /// graph is walked depth first, a mesh used by several nodes is imported once per node.
+    fn mesh_instances(&self) -> Vec<(gltf::Mesh<'_>, usize)> {
+        if !self.all_meshes {
+            return self
+                .gltf
+                .meshes()
+                .nth(self.mesh_idx.try_into().unwrap())
+                .map(|mesh| vec![(mesh, self.mesh_node as usize)])
+                .unwrap_or_default();
+        }
+
+        let scene = match self
+            .gltf
+            .default_scene()
+            .or_else(|| self.gltf.scenes().next())
+        {
+            Some(scene) => scene,
+            None => return Vec::new(),
+        };
+        let mut instances = Vec::new();
+        let mut stack: Vec<Node> = scene.nodes().collect();
+        stack.reverse();
+        while let Some(node) = stack.pop() {
+            if let Some(mesh) = node.mesh() {
+                instances.push((mesh, node.index()));
+            }
+            let mut children: Vec<Node> = node.children().collect();
+            children.reverse();
+            stack.append(&mut children);
+        }
+        instances
+    }
+
     fn check_node_name_uniqueness(&self) {
         if self.name_setting == ResolveNodeName::UniqueName {
             let error_msg = "
@@ -192,8 +433,8 @@ impl<'a> ImportedGltf {
     pub fn read_animation(
         &self,
         joints: &[RdJoint],
-        frames: usize,
-        _tmax: f32,
+        sampling: &AnimSampling,
+        selection: &[String],
     ) -> Option<Vec<RdAnim>> {
         let (gltf, buffers) = (&self.gltf, &self.buffers);
 
@@ -201,20 +442,34 @@ impl<'a> ImportedGltf {
         let mut rd_animations = Vec::new();
 
         let real_joints: HashSet<_> = joints.iter().map(|e| e.name.as_str()).collect();
-
-        let interpolate_error_message = "Interpolate required but not supported ! Re-Export model in blender with 'Always sample animations' enabled and try again";
+        // the anims are written next to the mesh, compared lowercase for case-insensitive file systems
+        let mut used_names: HashSet<String> = HashSet::new();
+        used_names.insert(MESH_FILE_STEM.to_lowercase());
+
+        for selector in selection {
+            let found = gltf
+                .animations()
+                .any(|a| anim_selected(a.index(), a.name(), std::slice::from_ref(selector)));
+            if !found {
+                warn!("No glTF animation with name or index '{}'", selector);
+            }
+        }
 
         for (anim_idx, animation) in gltf.animations().enumerate() {
+            if !anim_selected(anim_idx, animation.name(), selection) {
+                debug!("skip animation: {}", animation.name().unwrap_or("default"));
+                continue;
+            }
             let mut t_max = 0.0;
-            let mut interpolate_channel: HashMap<String, (Vec<Frame>, Vec<Frame>)> = HashMap::new();
 
             debug!("animation: {}", animation.name().unwrap_or("default"));
-            for (_, channel) in animation.channels().enumerate() {
+            for channel in animation.channels() {
                 let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
                 let time = reader.read_inputs().unwrap();
                 let output = reader.read_outputs().unwrap();
 
                 let target_node_name_v2 = self.node_get_name(&channel.target().node());
+                let interpolation = channel.sampler().interpolation();
 
                 debug!("{}", time.len());
                 info!(
@@ -231,7 +486,7 @@ impl<'a> ImportedGltf {
                         .unwrap()
                         .as_array()
                         .unwrap()
-                        .get(0)
+                        .first()
                         .unwrap()
                         .as_f64()
                         .unwrap(),
@@ -254,50 +509,66 @@ impl<'a> ImportedGltf {
                 if real_joints.contains(target_node_name_v2.as_str()) {
                     match output {
                         Rotations(rot) => {
-                            let mut rot_iter = rot.into_f32();
-                            if let Some(frames) = translation_map.get_mut(&target_node_name_v2) {
-                                for (frame, t) in frames.iter_mut().zip(time) {
-                                    if !relative_eq!(t, frame.time) {
-                                        interpolate_channel.insert(
-                                            target_node_name_v2,
-                                            (
-                                                frames.clone(),
-                                                read_animation_channel(buffers, channel),
-                                            ),
-                                        );
-                                        break;
+                            let mut keys = match linear_keys(interpolation, time, rot.into_f32()) {
+                                Some(keys) => keys,
+                                None => {
+                                    warn!("malformed CUBICSPLINE rotation sampler of node {}, skipping the channel", target_node_name_v2);
+                                    continue;
+                                }
+                            };
+                            if interpolation == Interpolation::CubicSpline {
+                                for (_, r) in keys.iter_mut() {
+                                    let q = Quaternion::from(Vector4::from(*r));
+                                    *r = q.normalize().coords.into();
+                                }
+                            }
+                            let rotations = extract_rotations(
+                                keys.iter().map(|k| k.0),
+                                origin_translation,
+                                keys.iter().map(|k| k.1),
+                            );
+                            match translation_map.get_mut(&target_node_name_v2) {
+                                Some(frames) if same_timeline(frames, &rotations) => {
+                                    for (frame, r) in frames.iter_mut().zip(rotations) {
+                                        frame.rotation = r.rotation;
                                     }
-                                    let r = rot_iter.next().unwrap();
-                                    frame.rotation = [r[0], r[1], r[2], -r[3]];
                                 }
-                            } else {
-                                translation_map.insert(
-                                    target_node_name_v2,
-                                    extract_rotations(time, origin_translation, rot_iter),
-                                );
+                                Some(frames) => {
+                                    debug!("resample channels: {}", target_node_name_v2);
+                                    *frames = resample_channels(frames, &rotations);
+                                }
+                                None => {
+                                    translation_map.insert(target_node_name_v2, rotations);
+                                }
                             }
                         }
-                        Translations(mut trans) => {
-                            if let Some(frames) = translation_map.get_mut(&target_node_name_v2) {
-                                for (frame, t) in frames.iter_mut().zip(time) {
-                                    if !relative_eq!(t, frame.time) {
-                                        interpolate_channel.insert(
-                                            target_node_name_v2,
-                                            (
-                                                read_animation_channel(buffers, channel),
-                                                frames.clone(),
-                                            ),
-                                        );
-                                        break;
-                                    }
-                                    frame.translation = trans.next().unwrap();
+                        Translations(trans) => {
+                            let keys = match linear_keys(interpolation, time, trans) {
+                                Some(keys) => keys,
+                                None => {
+                                    warn!("malformed CUBICSPLINE translation sampler of node {}, skipping the channel", target_node_name_v2);
+                                    continue;
                                 }
-                            } else {
-                                translation_map.insert(
-                                    target_node_name_v2,
-                                    extract_translations(time, origin_rotation, trans),
-                                );
                             };
+                            let translations = extract_translations(
+                                keys.iter().map(|k| k.0),
+                                origin_rotation,
+                                keys.iter().map(|k| k.1),
+                            );
+                            match translation_map.get_mut(&target_node_name_v2) {
+                                Some(frames) if same_timeline(frames, &translations) => {
+                                    for (frame, t) in frames.iter_mut().zip(translations) {
+                                        frame.translation = t.translation;
+                                    }
+                                }
+                                Some(frames) => {
+                                    debug!("resample channels: {}", target_node_name_v2);
+                                    *frames = resample_channels(&translations, frames);
+                                }
+                                None => {
+                                    translation_map.insert(target_node_name_v2, translations);
+                                }
+                            }
                         }
                         _ => {
                             warn!(
@@ -312,9 +583,38 @@ impl<'a> ImportedGltf {
                 }
             }
 
-            // TODO: finish interpolate
-            if interpolate_channel.drain().next().is_some() {
-                unimplemented!("{}", interpolate_error_message);
+            let start = sampling.start.map_or(0.0, f64::from);
+            let mut end = sampling.end.map_or(t_max, f64::from);
+            // a single pose, e.g. every key at t=0
+            let single_key = end <= start;
+            let grid = if single_key {
+                warn!(
+                    "animation time range {} - {} is empty, writing a single key at {}",
+                    start, end, start
+                );
+                end = start;
+                vec![start as f32]
+            } else {
+                sampling.grid(start as f32, end as f32)
+            };
+            let cropped = sampling.start.is_some() || sampling.end.is_some();
+            for frames in translation_map.values_mut() {
+                if sampling.resample || single_key {
+                    *frames = sample_channels(frames, frames, &grid);
+                } else if cropped {
+                    let mut times: Vec<f32> = frames
+                        .iter()
+                        .map(|f| f.time)
+                        .filter(|t| *t > grid[0] && *t < grid[grid.len() - 1])
+                        .collect();
+                    times.insert(0, grid[0]);
+                    times.push(grid[grid.len() - 1]);
+                    times.dedup_by(|a, b| relative_eq!(*a, *b));
+                    *frames = sample_channels(frames, frames, &times);
+                }
+                for frame in frames.iter_mut() {
+                    frame.time -= start as f32;
+                }
             }
 
             for joint in joints {
@@ -336,15 +636,14 @@ impl<'a> ImportedGltf {
                         origin_quaternio_raw.z,
                         origin_quaternio_raw.w,
                     ];
-                    let intervall = t_max as f32 / frames as f32;
-                    let mut v = Vec::with_capacity(frames);
-                    for i in 0..=frames {
-                        v.push(Frame {
+                    let v: Vec<Frame> = grid
+                        .iter()
+                        .map(|t| Frame {
                             rotation: origin_rotation,
                             translation: origin_translation,
-                            time: i as f32 * intervall,
-                        });
-                    }
+                            time: t - start as f32,
+                        })
+                        .collect();
                     warn!("idle_anim: adding idle for joint: {:?}", joint.name);
                     translation_map.insert(joint.name.clone(), v);
                 }
@@ -362,16 +661,35 @@ impl<'a> ImportedGltf {
 
             frame_collections.sort_by(|a, b| a.name.cmp(&b.name));
 
-            let name = format!("anim_{}", anim_idx);
+            let base_name = match animation.name() {
+                Some(name) if !name.is_empty() => file_name_safe(name),
+                _ => format!("anim_{}", anim_idx),
+            };
+            let mut name = base_name.clone();
+            let mut n = 0;
+            while !used_names.insert(name.to_lowercase()) {
+                n += 1;
+                name = format!("{}_{}", base_name, n);
+            }
+            if n > 0 {
+                warn!(
+                    "animation name {} is already used, writing it as {}",
+                    base_name, name
+                );
+            }
             rd_animations.push(RdAnim {
-                time_max: (t_max * 1000.0) as u32,
+                time_max: ((end - start) * 1000.0).round() as u32,
                 anim_vec: frame_collections,
                 name,
             });
         }
+        if rd_animations.is_empty() {
+            return None;
+        }
         Some(rd_animations)
     }
 
+    /// `None` if there is no mesh that can be imported, the reason is logged.
     pub fn gltf_to_rdm(
         &self,
         dst_format: TargetVertexFormat,
@@ -379,20 +697,18 @@ impl<'a> ImportedGltf {
         negative_x_and_v0v2v1: bool,
         no_transform: bool,
         overide_mesh_idx: Option<Vec<u32>>,
-    ) -> RdModell {
+    ) -> Option<RdModell> {
         if negative_x_and_v0v2v1 {
             warn!("negative_x_and_v0v2v1: {}", negative_x_and_v0v2v1);
             warn!("negative_x_and_v0v2v1 may cause lighting artifacts !");
         }
-        let gltf_imp = self
-            .read_mesh(
-                dst_format,
-                load_skin,
-                negative_x_and_v0v2v1,
-                no_transform,
-                overide_mesh_idx,
-            )
-            .unwrap();
+        let gltf_imp = self.read_mesh(
+            dst_format,
+            load_skin,
+            negative_x_and_v0v2v1,
+            no_transform,
+            overide_mesh_idx,
+        )?;
         let vertices = gltf_imp.1;
         let triangles = gltf_imp.2;
 
@@ -405,15 +721,151 @@ impl<'a> ImportedGltf {
 
         // todo!("TODO : FIX ME !!!");
         let mesh_info_vec = gltf_imp.4;
-        RdModell {
+        let materials = self.read_materials(&mesh_info_vec);
+        Some(RdModell {
             rdmf: None,
             mesh_info: mesh_info_vec,
             joints: joints_vec,
             triangle_indices: triangles,
+            bounding_box: vertices.bounding_box(),
+            materials,
+            export_info: Default::default(),
             vertex: vertices,
-            anim: None,
+            anims: Vec::new(),
             mat: None,
+        })
+    }
+
+    /// One material per rdm material slot. `mesh_info[i]` belongs to the i-th imported primitive.
+    fn read_materials(&self, mesh_info: &[MeshInfo]) -> Vec<RdmMaterialInfo> {
+        let instances = self.mesh_instances();
+        let primitives = instances
+            .iter()
+            .flat_map(|(mesh, _)| mesh.primitives())
+            .filter(has_triangles);
+
+        let texture_stems = self.texture_stems();
+
+        let slots = MeshInfo::get_max_material(mesh_info) as usize + 1;
+        let mut materials: Vec<Option<RdmMaterialInfo>> = vec![None; slots];
+        for (primitive, info) in primitives.zip(mesh_info) {
+            let slot = &mut materials[info.material as usize];
+            if slot.is_some() {
+                continue;
+            }
+
+            let material = primitive.material();
+            let name = material_name(&material);
+            let base_color = material.pbr_metallic_roughness().base_color_texture();
+            let texture_path = match base_color {
+                Some(_) if self.textures => {
+                    format!("{}_diff_0.dds", texture_stems[&material.index()])
+                }
+                _ => base_color
+                    .map(|info| {
+                        let image = info.texture().source();
+                        match image.source() {
+                            // embedded data uris are not a path
+                            gltf::image::Source::Uri { uri, .. } if !uri.starts_with("data:") => {
+                                uri.to_string()
+                            }
+                            _ => image.name().unwrap_or_default().to_string(),
+                        }
+                    })
+                    .unwrap_or_default(),
+            };
+            debug!("material slot {}: {} {}", info.material, name, texture_path);
+            *slot = Some(RdmMaterialInfo { name, texture_path });
+        }
+
+        materials
+            .into_iter()
+            .enumerate()
+            .map(|(i, m)| {
+                m.unwrap_or_else(|| RdmMaterialInfo {
+                    name: format!("material_{}", i),
+                    texture_path: String::new(),
+                })
+            })
+            .collect()
+    }
+
+    /// File name prefix of the textures of every glTF material index. Materials with the same
+    /// file safe name get a numbered suffix.
+    fn texture_stems(&self) -> HashMap<Option<usize>, String> {
+        let mut stems = HashMap::new();
+        let mut used = HashSet::new();
+        let instances = self.mesh_instances();
+        let primitives = instances
+            .iter()
+            .flat_map(|(mesh, _)| mesh.primitives())
+            .filter(has_triangles);
+        for primitive in primitives {
+            let material = primitive.material();
+            if stems.contains_key(&material.index()) {
+                continue;
+            }
+            let name = file_name_safe(&material_name(&material));
+            let mut stem = name.clone();
+            let mut n = 0;
+            while !used.insert(stem.clone()) {
+                n += 1;
+                stem = format!("{}_{}", name, n);
+            }
+            stems.insert(material.index(), stem);
         }
+        stems
+    }
+
+    /// Encodes the base color, normal and metallic textures of the mesh materials as
+    /// `<material>_diff_0.dds`, `<material>_norm_0.dds` and `<material>_metal_0.dds` in `dir`.
+    /// Materials with the same name are written as `<material>_1_diff_0.dds`, ...
+    pub fn write_textures(&self, dir: &Path, create_new: bool) -> Vec<PathBuf> {
+        let mut written = Vec::new();
+        let stems = self.texture_stems();
+        let mut seen = HashSet::new();
+        let instances = self.mesh_instances();
+        let primitives = instances
+            .iter()
+            .flat_map(|(mesh, _)| mesh.primitives())
+            .filter(has_triangles);
+        for primitive in primitives {
+            let material = primitive.material();
+            if !seen.insert(material.index()) {
+                continue;
+            }
+            let name = &stems[&material.index()];
+            let pbr = material.pbr_metallic_roughness();
+            let textures = [
+                ("diff", pbr.base_color_texture().map(|t| t.texture())),
+                ("norm", material.normal_texture().map(|t| t.texture())),
+                (
+                    "metal",
+                    pbr.metallic_roughness_texture().map(|t| t.texture()),
+                ),
+            ];
+            for (kind, texture) in textures {
+                let Some(texture) = texture else {
+                    continue;
+                };
+                let data = &self.images[texture.source().index()];
+                let image = DdsImage::from_rgba(data.width, data.height, image_to_rgba(data));
+
+                let file_path = dir.join(format!("{}_{}_0.dds", name, kind));
+                info!("write {:?}", file_path);
+                let dds = image.to_dds().expect("DDS encoding failed");
+                let mut writer = OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .create_new(create_new)
+                    .open(&file_path)
+                    .expect("I/O error");
+                writer.write_all(&dds).expect("I/O error");
+                written.push(file_path);
+            }
+        }
+        written
     }
 
     fn read_skin(&self) -> Vec<RdJoint> {
@@ -513,32 +965,104 @@ impl<'a> ImportedGltf {
         &self,
         dst_format: TargetVertexFormat,
         read_joints: bool,
-        mut negative_x_and_v0v2v1: bool,
+        mirror_x: bool,
         no_transform: bool,
         overide_mesh_idx: Option<Vec<u32>>,
     ) -> ReadMeshOutput {
         let (gltf, buffers) = (&self.gltf, &self.buffers);
-        // only the nth mesh of file gets read
-        if let Some(mesh) = gltf.meshes().nth(self.mesh_idx.try_into().unwrap()) {
-            info!("Mesh #{}", mesh.index());
+        let instances = self.mesh_instances();
+        if instances.is_empty() {
+            return None;
+        }
+
+        let ident = dst_format.identifiers.to_vec();
+        // index of the attribute set (e.g. TEXCOORD_n) each component is read from
+        let sets: Vec<usize> = ident
+            .iter()
+            .enumerate()
+            .map(|(i, x)| ident[..i].iter().filter(|y| y.uniq == x.uniq).count())
+            .collect();
+        let set_count = |uniq: UniqueIdentifier| ident.iter().filter(|x| x.uniq == uniq).count();
+        // number of joint influences per vertex
+        let skin_slots = 4 * set_count(UniqueIdentifier::I4b).max(set_count(UniqueIdentifier::W4b));
+        let needs_normal = dst_format.contains(UniqueIdentifier::Normal)
+            || dst_format.contains(UniqueIdentifier::Bitangent);
+        let needs_tangent = dst_format.contains(UniqueIdentifier::GTangent)
+            || dst_format.contains(UniqueIdentifier::Bitangent);
+        let vertsize = ident.iter().map(|x| x.get_size()).sum();
+
+        let mut mesh_info: Vec<MeshInfo> = Vec::new();
+        let mut merged_triangle_vec = Vec::new();
+        let mut vertices_count: u32 = 0;
+        let mut verts_vec = BytesMut::with_capacity(64000 * vertsize as usize);
+
+        let mut kown_vbuffers = HashMap::new();
+
+        let primitive_count = instances
+            .iter()
+            .flat_map(|(mesh, _)| mesh.primitives())
+            .filter(has_triangles)
+            .count();
+        if primitive_count == 0 {
+            error!("The glTF mesh has no triangle primitives.");
+            return None;
+        }
+        if let Some(v) = overide_mesh_idx.as_ref() {
+            assert_eq!(primitive_count, v.len());
+        }
+        // the joint indices of every mesh refer to the joints of the skin read by read_skin
+        if read_joints {
+            let node_skin = |node: usize| gltf.nodes().nth(node).and_then(|n| n.skin());
+            let skin = node_skin(self.mesh_node as usize).map(|s| s.index());
+            for (mesh, node) in &instances {
+                if let Some(other) = node_skin(*node).filter(|s| Some(s.index()) != skin) {
+                    error!(
+                        "Mesh #{} of node {} is bound to skin #{}, but the joints are read from skin {:?} of node {}. Only meshes of the same skin can be imported together.",
+                        mesh.index(),
+                        node,
+                        other.index(),
+                        skin,
+                        self.mesh_node
+                    );
+                    return None;
+                }
+            }
+        }
+        // rdm material slot of every glTF material index in order of first use
+        let mut material_slots: HashMap<Option<usize>, u32> = HashMap::new();
+        let mut primitive_idx = 0;
 
-            let mesh_instantiating_node = self.mesh_node.try_into().unwrap();
+        for (mesh, mesh_instantiating_node) in instances {
+            info!("Mesh #{}", mesh.index());
             debug!("mesh_instantiating_node: {}", mesh_instantiating_node);
 
+            // glTF ignores the node transform of skinned meshes, they are placed like the mesh
+            // the joints are read for
+            let skinned = read_joints
+                && gltf
+                    .nodes()
+                    .nth(mesh_instantiating_node)
+                    .and_then(|n| n.skin())
+                    .is_some();
+            let transform_node = if skinned {
+                self.mesh_node as usize
+            } else {
+                mesh_instantiating_node
+            };
             let mut base: Matrix4<f32> = if no_transform {
                 Matrix4::identity()
             } else {
-                build_transform2(gltf, mesh_instantiating_node)
+                build_transform2(gltf, transform_node)
             };
 
-            if negative_x_and_v0v2v1 {
+            if mirror_x {
                 let m = Matrix3::new(-1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0);
                 base *= m.to_homogeneous();
-                negative_x_and_v0v2v1 = false;
             }
 
             debug!("base: {}", &base);
 
+            let mut negative_x_and_v0v2v1 = false;
             let det = base.determinant();
             if det.is_sign_negative() {
                 warn!("determinant is negative: {}", det);
@@ -546,108 +1070,188 @@ impl<'a> ImportedGltf {
                 negative_x_and_v0v2v1 = true;
             }
 
+            // raw bitangents are only kept as they are without a transform
+            let transform_raw_bitangent = base != Matrix4::identity();
+
             let mat3 = base.fixed_resize::<3, 3>(0.0);
             let inv_transform_mat3 = mat3.try_inverse().unwrap();
             let transpose_inv_transform_mat3 = inv_transform_mat3.transpose();
 
-            let ident = match dst_format {
-                TargetVertexFormat::P4h_N4b_G4b_B4b_T2h => {
-                    crate::vertex::p4h_n4b_g4b_b4b_t2h().to_vec()
+            for primitive in mesh.primitives() {
+                if !has_triangles(&primitive) {
+                    warn!(
+                        "Skipping primitive #{} with mode {:?}, only triangles can be imported.",
+                        primitive.index(),
+                        primitive.mode()
+                    );
+                    continue;
                 }
-                TargetVertexFormat::P4h_N4b_G4b_B4b_T2h_I4b => {
-                    crate::vertex::p4h_n4b_g4b_b4b_t2h_i4b().to_vec()
-                }
-                TargetVertexFormat::P4h_N4b_G4b_B4b_T2h_I4b_W4b => {
-                    crate::vertex::p4h_n4b_g4b_b4b_t2h_i4b_w4b().to_vec()
-                }
-            };
-            let vertsize = ident.iter().map(|x| x.get_size()).sum();
-
-            let mut mesh_info: Vec<MeshInfo> = Vec::new();
-            let mut merged_triangle_vec = Vec::new();
-            let mut vertices_count: u32 = 0;
-            let mut verts_vec = BytesMut::with_capacity(64000 * vertsize as usize);
-
-            let mut kown_vbuffers = HashMap::new();
-
-            if let Some(v) = overide_mesh_idx.as_ref() {
-                assert_eq!(mesh.primitives().len(), v.len());
-            }
-            for (i, primitive) in mesh.primitives().enumerate() {
+                let i = primitive_idx;
+                primitive_idx += 1;
                 info!("- Primitive #{}", primitive.index());
                 let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
 
-                let mut position_iter = reader.read_positions().unwrap();
-                let mut count = position_iter.len();
-                let normal_it = match reader.read_normals() {
+                let mut positions: Vec<[f32; 3]> = reader.read_positions().unwrap().collect();
+                let mut count = positions.len();
+                let mut indices = triangle_list(
+                    primitive.mode(),
+                    reader.read_indices().map(|i| i.into_u32().collect()),
+                    count,
+                );
+                // glTF vertex of every written vertex if generated normals or tangents split vertices
+                let mut vertex_source: Option<Vec<u32>> = None;
+                let mut normal_vec: Vec<[f32; 3]> = match reader.read_normals() {
                     Some(iter) => iter.collect(),
-                    None => {
-                        error!("Model has no normals! Enable normal export in Blender! Non existing normal will cause garbage values!");
-                        vec![[0.0f32, 0.0f32, 1.0f32]]
+                    None if needs_normal || needs_tangent => {
+                        info!(
+                            "Model has no normals, generating {:?} normals.",
+                            self.normal_mode
+                        );
+                        let generated = generate_normals(
+                            &positions,
+                            &indices,
+                            self.normal_mode,
+                            self.normal_angle,
+                        );
+                        positions = remap(positions, Some(&generated.vertex_source));
+                        indices = generated.indices;
+                        vertex_source = Some(generated.vertex_source);
+                        generated.normals
                     }
+                    None => vec![],
                 };
-                let mut normal_iter = normal_it.into_iter().cycle();
 
-                let tangent_it = match reader.read_tangents() {
-                    Some(iter) => iter.collect(),
-                    None => {
-                        error!("Model has no tangents! Enable tangent export in Blender! Non existing tangents will cause garbage values!");
-                        vec![[0.0f32, 0.0f32, 0.0f32, 1.0f32]]
+                let tangent_vec: Vec<[f32; 4]> = match reader.read_tangents() {
+                    Some(iter) => remap(iter.collect(), vertex_source.as_deref()),
+                    None if needs_tangent => {
+                        let tex_coords: Option<Vec<[f32; 2]>> = reader
+                            .read_tex_coords(0)
+                            .map(|tex| remap(tex.into_f32().collect(), vertex_source.as_deref()));
+                        let generated = match tex_coords {
+                            Some(tex) if !normal_vec.is_empty() => {
+                                info!("Model has no tangents, generating MikkTSpace tangents.");
+                                generate_tangents(&positions, &normal_vec, &tex, &indices)
+                            }
+                            _ => None,
+                        };
+                        match generated {
+                            Some(generated) => {
+                                // vertices with different tangents at mirrored uv seams are split
+                                let split = Some(generated.vertex_source.as_slice());
+                                positions = remap(positions, split);
+                                normal_vec = remap(normal_vec, split);
+                                indices = generated.indices;
+                                vertex_source = Some(match vertex_source {
+                                    Some(source) => remap(source, split),
+                                    None => generated.vertex_source,
+                                });
+                                generated.tangents
+                            }
+                            None => {
+                                error!("Model has no tangents and they cannot be generated without normals and texcoord_0! Non existing tangents will cause garbage values!");
+                                vec![]
+                            }
+                        }
                     }
+                    None => vec![],
                 };
-                let mut tangent_iter = tangent_it.into_iter().cycle();
-
-                let tex_iter1 = reader.read_tex_coords(0);
-                let p: Vec<[f32; 2]> = match tex_iter1 {
-                    Some(tex) => {
-                        let r: Vec<[f32; 2]> = tex.into_f32().collect();
-                        assert_eq!(count, r.len());
-                        r
-                    }
-                    None => {
-                        error!(
-                            "No tex_coords ! Non existing 'texcoord_0' will cause garbage values!"
-                        );
-                        vec![[0.0f32, 0.0f32]]
+                let vertex_source = vertex_source.as_deref();
+
+                // raw B4b data written by the rdm to glTF export
+                let bitangent_vec: Vec<[u8; 4]> = match primitive
+                    .get(&gltf::Semantic::Extras(BITANGENT_ATTRIBUTE.into()))
+                    .filter(|_| dst_format.contains(UniqueIdentifier::Bitangent))
+                {
+                    Some(accessor) => {
+                        gltf::accessor::Iter::new(accessor, |buffer| Some(&buffers[buffer.index()]))
+                            .map(|iter| remap(iter.collect(), vertex_source))
+                            .unwrap_or_default()
                     }
+                    None => vec![],
                 };
-                let mut tex_iter = p.into_iter().cycle();
 
-                let jvecarr: Vec<[u16; 4]> = match reader.read_joints(0) {
-                    Some(joints) if read_joints => {
+                let tex_vec: Vec<Vec<[f32; 2]>> = (0..set_count(UniqueIdentifier::Texcoord))
+                    .map(|set| match reader.read_tex_coords(set as u32) {
+                        Some(tex) => {
+                            let r: Vec<[f32; 2]> = tex.into_f32().collect();
+                            assert_eq!(count, r.len());
+                            remap(r, vertex_source)
+                        }
+                        None => {
+                            error!(
+                                "No tex_coords ! Non existing 'texcoord_{}' will cause garbage values!",
+                                set
+                            );
+                            vec![]
+                        }
+                    })
+                    .collect();
+
+                let color_vec: Vec<Vec<[f32; 4]>> = (0..set_count(UniqueIdentifier::C4c))
+                    .map(|set| match reader.read_colors(set as u32) {
+                        Some(colors) => {
+                            let c: Vec<[f32; 4]> = colors.into_rgba_f32().collect();
+                            assert_eq!(count, c.len());
+                            remap(c, vertex_source)
+                        }
+                        None => {
+                            warn!(
+                                "No COLOR_{} in glTF file! Vertex colour defaults to white.",
+                                set
+                            );
+                            vec![]
+                        }
+                    })
+                    .collect();
+
+                // every JOINTS_n/WEIGHTS_n set of the glTF is read, the largest weights are
+                // distributed over the I4b/W4b components of the target format.
+                let mut joint_vec: Vec<Vec<[u8; 4]>> = Vec::new();
+                let mut weight_vec: Vec<Vec<[f32; 4]>> = Vec::new();
+                if read_joints && skin_slots > 0 {
+                    while let Some(joints) = reader.read_joints(joint_vec.len() as u32) {
                         let j: Vec<[u16; 4]> = joints.into_u16().collect();
                         assert_eq!(count, j.len());
-                        j
+                        let j = match joint_indices(&j) {
+                            Ok(j) => j,
+                            Err(joint) => {
+                                error!(
+                                    "JOINTS_{} references joint {}, rdm vertices can only reference the joints 0 to 255.",
+                                    joint_vec.len(),
+                                    joint
+                                );
+                                return None;
+                            }
+                        };
+                        joint_vec.push(remap(j, vertex_source));
                     }
-                    _ => {
-                        warn!("No joints in glTF file !");
-                        if read_joints {
-                            panic!("No joints in glTF file but --skeleton flag was set!")
-                        }
-                        vec![[0, 0, 0, 0]]
+                    while let Some(weights) = reader.read_weights(weight_vec.len() as u32) {
+                        let w: Vec<[f32; 4]> = weights.into_f32().collect();
+                        assert_eq!(count, w.len());
+                        weight_vec.push(remap(w, vertex_source));
                     }
-                };
-
-                let mut joints_iter = jvecarr.into_iter().cycle();
-
-                let wvecarr: Vec<[f32; 4]> = match reader.read_weights(0) {
-                    Some(weight) if read_joints => {
-                        let j: Vec<[f32; 4]> = weight.into_f32().collect();
-                        assert_eq!(count, j.len());
-                        j
+                }
+                if dst_format.contains(UniqueIdentifier::I4b) && joint_vec.is_empty() {
+                    warn!("No joints in glTF file !");
+                    if read_joints {
+                        panic!("No joints in glTF file but --skeleton flag was set!")
                     }
-                    _ => {
-                        warn!("No weights in glTF file !");
-                        if read_joints {
-                            panic!("No joints/weights in glTF file but --skeleton flag was set!")
-                        }
-                        vec![[0.0, 0.0, 0.0, 0.0]]
+                }
+                if dst_format.contains(UniqueIdentifier::W4b) && weight_vec.is_empty() {
+                    warn!("No weights in glTF file !");
+                    if read_joints {
+                        panic!("No joints/weights in glTF file but --skeleton flag was set!")
                     }
-                };
-
-                let mut weights_iter = wvecarr.into_iter().cycle();
+                }
+                if 4 * joint_vec.len() > skin_slots {
+                    warn!(
+                        "glTF has {} joint influences per vertex, only the {} largest are kept.",
+                        4 * joint_vec.len(),
+                        skin_slots
+                    );
+                }
 
-                info!("dst_format: {:?}", dst_format);
+                info!("dst_format: {}", dst_format);
                 //let mut verts_vec = BytesMut::with_capacity(count * vertsize as usize);
 
                 debug!("vertex read loop");
@@ -655,23 +1259,20 @@ impl<'a> ImportedGltf {
 
                 let pre_vertices_added = verts_vec.len();
 
+                // split vertices of generated normals
+                count = positions.len();
+                let mut vertex_idx = 0;
                 while count > 0 {
                     debug!("count {}", count);
-                    let vertex_position = position_iter.next().unwrap();
+                    let vertex_position = positions[vertex_idx];
                     let vertex =
                         Point3::new(vertex_position[0], vertex_position[1], vertex_position[2]);
                     let transformed_vertex = base.transform_point(&vertex);
 
-                    let p4h = P4h {
-                        data: [
-                            f16::from_f32(1.0 * transformed_vertex[0]),
-                            f16::from_f32(1.0 * transformed_vertex[1]),
-                            f16::from_f32(1.0 * transformed_vertex[2]),
-                            f16::from_f32(0.0),
-                        ],
-                    };
-
-                    let normals = normal_iter.next().unwrap();
+                    let normals = normal_vec
+                        .get(vertex_idx)
+                        .copied()
+                        .unwrap_or([0.0, 0.0, 1.0]);
                     let normv: Vector3<f32> = Vector3::new(normals[0], normals[1], normals[2]);
                     let transformed_normals: Vector3<f32> = transpose_inv_transform_mat3 * normv;
 
@@ -686,16 +1287,10 @@ impl<'a> ImportedGltf {
                     ny /= len;
                     nz /= len;
 
-                    let n4b = N4b {
-                        data: [
-                            (((nx + 1.0) / 2.0) * 255.0).round() as u8,
-                            (((ny + 1.0) / 2.0) * 255.0).round() as u8,
-                            (((nz + 1.0) / 2.0) * 255.0).round() as u8,
-                            0,
-                        ],
-                    };
-
-                    let tangents = tangent_iter.next().unwrap();
+                    let tangents = tangent_vec
+                        .get(vertex_idx)
+                        .copied()
+                        .unwrap_or([0.0, 0.0, 0.0, 1.0]);
                     let tangv = Vector3::new(tangents[0], tangents[1], tangents[2]);
                     let transformed_tangents = transpose_inv_transform_mat3 * tangv;
 
@@ -718,74 +1313,61 @@ impl<'a> ImportedGltf {
                     };
                     //assert_relative_eq!(tw.abs(), 1.0);
 
-                    let g4b = G4b {
-                        data: [
-                            (((tx + 1.0) / 2.0) * 255.0).round() as u8,
-                            (((ty + 1.0) / 2.0) * 255.0).round() as u8,
-                            (((tz + 1.0) / 2.0) * 255.0).round() as u8,
-                            0,
-                        ],
-                    };
-
                     let normal = Vector3::new(nx, ny, nz);
                     let tangent = Vector3::new(tx, ty, tz);
                     debug!("normal.dot(&tangent): {}", normal.dot(&tangent));
 
                     let b: Matrix3x1<f32> = (normal.cross(&tangent)) * (tw);
 
-                    let b4b = B4b {
-                        data: [
-                            (((b.x + 1.0) / 2.0) * 255.0).round() as u8,
-                            (((b.y + 1.0) / 2.0) * 255.0).round() as u8,
-                            (((b.z + 1.0) / 2.0) * 255.0).round() as u8,
-                            0,
-                        ],
-                    };
-
-                    // tex
-
-                    let tex = tex_iter.next().unwrap();
-                    //let tex = [0.0, 0.0];
-                    let t2h = T2h {
-                        data: [f16::from_f32(tex[0]), f16::from_f32(tex[1])],
-                    };
-
-                    verts_vec.put_vertex_data(&p4h);
-                    verts_vec.put_vertex_data(&n4b);
-                    verts_vec.put_vertex_data(&g4b);
-                    verts_vec.put_vertex_data(&b4b);
-                    verts_vec.put_vertex_data(&t2h);
-                    // TODO clean up checks
-                    if dst_format == TargetVertexFormat::P4h_N4b_G4b_B4b_T2h_I4b
-                        || dst_format == TargetVertexFormat::P4h_N4b_G4b_B4b_T2h_I4b_W4b
-                    {
-                        // joints idx
-                        let joint = joints_iter.next().unwrap();
-
-                        let i4b = I4b {
-                            data: [
-                                joint[0] as u8,
-                                joint[1] as u8,
-                                joint[2] as u8,
-                                joint[3] as u8,
-                            ],
-                        };
-                        verts_vec.put_vertex_data(&i4b);
-                    }
-
-                    if dst_format == TargetVertexFormat::P4h_N4b_G4b_B4b_T2h_I4b_W4b {
-                        let weight = weights_iter.next().unwrap();
-                        let w4b = W4b {
-                            data: [
-                                (weight[0] * 255.0).round() as u8,
-                                (weight[1] * 255.0).round() as u8,
-                                (weight[2] * 255.0).round() as u8,
-                                (weight[3] * 255.0).round() as u8,
+                    let influences =
+                        vertex_influences(&joint_vec, &weight_vec, vertex_idx, skin_slots);
+
+                    for (component, &set) in ident.iter().zip(sets.iter()) {
+                        // the unused 4th byte of normal, tangent and bitangent is stored as 0
+                        let values = match component.uniq {
+                            UniqueIdentifier::Position => [
+                                transformed_vertex[0],
+                                transformed_vertex[1],
+                                transformed_vertex[2],
+                                0.0,
                             ],
+                            UniqueIdentifier::Normal => [nx, ny, nz, -1.0],
+                            UniqueIdentifier::GTangent => [tx, ty, tz, -1.0],
+                            UniqueIdentifier::Bitangent => match bitangent_vec.get(vertex_idx) {
+                                Some(raw) => {
+                                    let raw = raw.map(|c| f32::from(c) / 255.0 * 2.0 - 1.0);
+                                    if transform_raw_bitangent {
+                                        let v = Vector3::new(raw[0], raw[1], raw[2]);
+                                        let v = transpose_inv_transform_mat3 * v;
+                                        let v = v.try_normalize(f32::EPSILON).unwrap_or(v);
+                                        [v.x, v.y, v.z, raw[3]]
+                                    } else {
+                                        raw
+                                    }
+                                }
+                                None => [b.x, b.y, b.z, -1.0],
+                            },
+                            UniqueIdentifier::Texcoord => {
+                                let tex = tex_vec[set].get(vertex_idx).copied().unwrap_or_default();
+                                [tex[0], tex[1], 0.0, 0.0]
+                            }
+                            UniqueIdentifier::C4c => {
+                                color_vec[set].get(vertex_idx).copied().unwrap_or([1.0; 4])
+                            }
+                            UniqueIdentifier::I4b => {
+                                let i = &influences[4 * set..4 * set + 4];
+                                [0, 1, 2, 3].map(|k| f32::from(i[k].0))
+                            }
+                            UniqueIdentifier::W4b => {
+                                let i = &influences[4 * set..4 * set + 4];
+                                [0, 1, 2, 3].map(|k| f32::from(i[k].1) / 255.0)
+                            }
+                            UniqueIdentifier::Invalid => unreachable!(),
                         };
-                        verts_vec.put_vertex_data(&w4b);
+                        verts_vec.put_component(component, values);
                     }
 
+                    vertex_idx += 1;
                     count -= 1;
                 }
 
@@ -806,19 +1388,16 @@ impl<'a> ImportedGltf {
 
                 vertices_count = verts_vec.len() as u32 / vertsize;
                 info!("Vertex count: {}", vertices_count);
-                if vertices_count > (u16::MAX - 1).into() {
-                    error!("Mesh consists of too many vertices ({})!", vertices_count);
-                    error!("Mesh vertices count must stay below 65535!");
-                    error!("The vertex count that Max/Maya/Blender show may not reflect the reality of the glTF.");
-                    error!("E.g. during Blender's glTF export, shared vertices may need to be unshared (duplicated again) if not all vertex attributes (normals, tangents, UV) are exactly the same!")
+                if vertices_count > u32::from(u16::MAX) + 1 {
+                    info!("More than 65536 vertices: rdm will use 32-bit indices.");
                 }
 
                 //let verts = VertexFormat2::new(ident, vertices_count, vertsize, 0, verts_vec.freeze());
 
-                let mut triangle_iter = reader.read_indices().unwrap().into_u32();
+                let mut triangle_iter = indices.iter().copied();
                 let mut triangle_vec: Vec<Triangle> = Vec::with_capacity(count);
 
-                let mut tcount = triangle_iter.len() / 3;
+                let mut tcount = indices.len() / 3;
 
                 while tcount > 0 {
                     //let ctri = triangle_iter.next().unwrap();
@@ -851,6 +1430,12 @@ impl<'a> ImportedGltf {
                     index_count: triangle_vec.len() as u32 * 3,
                     material: match overide_mesh_idx.as_ref() {
                         Some(j) => j[i],
+                        None if self.all_meshes => {
+                            let next = material_slots.len() as u32;
+                            *material_slots
+                                .entry(primitive.material().index())
+                                .or_insert(next)
+                        }
                         None => i.try_into().unwrap(),
                     },
                     _padding: Default::default(),
@@ -859,26 +1444,133 @@ impl<'a> ImportedGltf {
                 merged_triangle_vec.append(&mut triangle_vec);
 
                 info!("{:?}", &mesh_info);
-                //return Some((vertsize, verts, merged_triangle_vec, vertices_count, mesh_info));
             }
-            let verts = VertexFormat2::new(
-                ident.into_boxed_slice(),
-                vertices_count,
-                vertsize,
-                verts_vec.freeze(),
-            );
-            return Some((
-                vertsize,
-                verts,
-                merged_triangle_vec,
-                vertices_count,
-                mesh_info,
-            ));
         }
-        None
+        let verts = VertexFormat2::new(
+            ident.into_boxed_slice(),
+            vertices_count,
+            vertsize,
+            verts_vec.freeze(),
+        );
+        Some((
+            vertsize,
+            verts,
+            merged_triangle_vec,
+            vertices_count,
+            mesh_info,
+        ))
     }
 }
 
+fn material_name(material: &gltf::Material) -> String {
+    match (material.name(), material.index()) {
+        (Some(name), _) => name.to_string(),
+        (None, Some(idx)) => format!("material_{}", idx),
+        (None, None) => String::from("default"),
+    }
+}
+
+/// Expands the decoded glTF image to 8 bit RGBA.
+fn image_to_rgba(data: &gltf::image::Data) -> Vec<u8> {
+    use gltf::image::Format;
+    let u16_to_u8 = |p: &[u8]| (u16::from_ne_bytes([p[0], p[1]]) >> 8) as u8;
+    let f32_to_u8 = |p: &[u8]| {
+        (f32::from_ne_bytes([p[0], p[1], p[2], p[3]]).clamp(0.0, 1.0) * 255.0).round() as u8
+    };
+    let pixels = &data.pixels;
+    match data.format {
+        Format::R8 => pixels.iter().flat_map(|&l| [l, l, l, 255]).collect(),
+        Format::R8G8 => pixels
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        Format::R8G8B8 => pixels
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        Format::R8G8B8A8 => pixels.clone(),
+        Format::R16 => pixels
+            .chunks_exact(2)
+            .flat_map(|p| {
+                let l = u16_to_u8(p);
+                [l, l, l, 255]
+            })
+            .collect(),
+        Format::R16G16 => pixels
+            .chunks_exact(4)
+            .flat_map(|p| {
+                let l = u16_to_u8(p);
+                [l, l, l, u16_to_u8(&p[2..])]
+            })
+            .collect(),
+        Format::R16G16B16 => pixels
+            .chunks_exact(6)
+            .flat_map(|p| [u16_to_u8(p), u16_to_u8(&p[2..]), u16_to_u8(&p[4..]), 255])
+            .collect(),
+        Format::R16G16B16A16 => pixels.chunks_exact(2).map(u16_to_u8).collect(),
+        Format::R32G32B32FLOAT => pixels
+            .chunks_exact(12)
+            .flat_map(|p| [f32_to_u8(p), f32_to_u8(&p[4..]), f32_to_u8(&p[8..]), 255])
+            .collect(),
+        Format::R32G32B32A32FLOAT => pixels.chunks_exact(4).map(f32_to_u8).collect(),
+    }
+}
+
+/// Joint indices of a glTF `JOINTS_n` set as I4b bytes, or the first index that does not fit.
+fn joint_indices(joints: &[[u16; 4]]) -> Result<Vec<[u8; 4]>, u16> {
+    joints
+        .iter()
+        .map(|j| {
+            let mut out = [0; 4];
+            for (dst, &joint) in out.iter_mut().zip(j) {
+                *dst = u8::try_from(joint).map_err(|_| joint)?;
+            }
+            Ok(out)
+        })
+        .collect()
+}
+
+/// Collects the joint influences of vertex `idx` from all glTF joint/weight sets, keeps the
+/// `slots` largest and quantises their weights so that they sum up to 255.
+fn vertex_influences(
+    joints: &[Vec<[u8; 4]>],
+    weights: &[Vec<[f32; 4]>],
+    idx: usize,
+    slots: usize,
+) -> Vec<(u8, u8)> {
+    let mut influences: Vec<(u8, f32)> = Vec::with_capacity(4 * joints.len());
+    for (set, j) in joints.iter().enumerate() {
+        // without weights all joints are weighted equally
+        let w = weights.get(set).map_or([1.0; 4], |w| w[idx]);
+        influences.extend(j[idx].iter().copied().zip(w).filter(|(_, w)| *w > 0.0));
+    }
+    influences.sort_by(|a, b| b.1.total_cmp(&a.1));
+    influences.truncate(slots);
+
+    let mut out = vec![(0, 0); slots];
+    let total: f32 = influences.iter().map(|(_, w)| w).sum();
+    if total <= 0.0 {
+        return out;
+    }
+
+    // largest remainder: round down, then hand out the rest to the largest fractions
+    let exact: Vec<f32> = influences.iter().map(|(_, w)| w / total * 255.0).collect();
+    let mut remainder = 255 - exact.iter().map(|w| w.floor() as u32).sum::<u32>();
+    let mut by_fraction: Vec<usize> = (0..exact.len()).collect();
+    by_fraction.sort_by(|&a, &b| exact[b].fract().total_cmp(&exact[a].fract()));
+    for (i, (joint, _)) in influences.iter().enumerate() {
+        out[i] = (*joint, exact[i].floor() as u8);
+    }
+    for i in by_fraction {
+        if remainder == 0 {
+            break;
+        }
+        out[i].1 += 1;
+        remainder -= 1;
+    }
+    out
+}
+
 #[inline]
 fn create_joint(mut mat4_init: Matrix4<f32>, name: String, parent: u32) -> RdJoint {
     // may perform expensive checks ...
@@ -1001,3 +1693,127 @@ fn find_parent(mesh_node: usize, child_list: &[Option<Vec<usize>>]) -> Option<us
     }
     parent_idx
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn influences_top_n_sum_to_255() {
+        let joints = vec![vec![[1, 2, 3, 4]], vec![[5, 6, 0, 0]]];
+        let weights = vec![vec![[0.1, 0.3, 0.05, 0.2]], vec![[0.25, 0.1, 0.0, 0.0]]];
+        let influences = vertex_influences(&joints, &weights, 0, 4);
+        assert_eq!(
+            influences.iter().map(|(j, _)| *j).collect::<Vec<_>>(),
+            [2, 5, 4, 1]
+        );
+        assert_eq!(influences.iter().map(|(_, w)| *w as u32).sum::<u32>(), 255);
+
+        let influences = vertex_influences(&joints, &weights, 0, 8);
+        assert_eq!(influences.len(), 8);
+        assert_eq!(influences[6..], [(0, 0), (0, 0)]);
+        assert_eq!(influences.iter().map(|(_, w)| *w as u32).sum::<u32>(), 255);
+
+        let weights = vec![vec![[f32::NAN, 0.5, 0.5, 0.0]]];
+        let influences = vertex_influences(&joints[..1], &weights, 0, 4);
+        assert_eq!(influences.iter().map(|(_, w)| *w as u32).sum::<u32>(), 255);
+    }
+
+    #[test]
+    fn joint_indices_fit_i4b() {
+        assert_eq!(joint_indices(&[[0, 1, 255, 3]]), Ok(vec![[0, 1, 255, 3]]));
+        assert_eq!(joint_indices(&[[0, 1, 2, 3], [4, 256, 300, 0]]), Err(256));
+    }
+    #[test]
+    fn resample_mismatched_channels() {
+        let frame = |time, rotation, translation| Frame {
+            rotation,
+            translation,
+            time,
+        };
+        let id = [0.0, 0.0, 0.0, 1.0];
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        let translations = [
+            frame(0.0, id, [0.0, 0.0, 0.0]),
+            frame(1.0, id, [2.0, 4.0, 0.0]),
+        ];
+        let rotations = [
+            frame(0.0, id, [0.0; 3]),
+            frame(0.25, id, [0.0; 3]),
+            frame(0.75, [0.0, 0.0, half, half], [0.0; 3]),
+        ];
+
+        let frames = resample_channels(&translations, &rotations);
+        let times: Vec<f32> = frames.iter().map(|f| f.time).collect();
+        assert_eq!(times, [0.0, 0.25, 0.75, 1.0]);
+        assert_eq!(frames[1].translation, [0.5, 1.0, 0.0]);
+        assert_eq!(frames[2].translation, [1.5, 3.0, 0.0]);
+        // rotation is held after its last keyframe
+        assert_relative_eq!(frames[3].rotation[2], half);
+        assert_relative_eq!(frames[3].rotation[3], half);
+
+        // slerp halfway between identity and 90° around z
+        let frames = resample_channels(&[frame(0.5, id, [0.0; 3])], &rotations);
+        let eighth = std::f32::consts::FRAC_PI_8;
+        assert_eq!(frames.len(), 4);
+        assert_relative_eq!(frames[2].rotation[2], eighth.sin(), epsilon = 1e-6);
+        assert_relative_eq!(frames[2].rotation[3], eighth.cos(), epsilon = 1e-6);
+    }
+    #[test]
+    fn step_and_cubicspline_keys() {
+        let time = [0.0, 1.0, 2.0];
+        let values = [[1.0], [2.0], [4.0]];
+        let keys = linear_keys(Interpolation::Step, time.into_iter(), values.into_iter()).unwrap();
+        assert_eq!(
+            keys,
+            [
+                (0.0, [1.0]),
+                (1.0, [1.0]),
+                (1.0, [2.0]),
+                (2.0, [2.0]),
+                (2.0, [4.0])
+            ]
+        );
+
+        // (in-tangent, value, out-tangent) with flat tangents
+        let values = [[0.0], [1.0], [0.0], [0.0], [3.0], [0.0]];
+        let keys = linear_keys(
+            Interpolation::CubicSpline,
+            time[..2].iter().copied(),
+            values.into_iter(),
+        )
+        .unwrap();
+        assert_eq!(keys.len(), CUBICSPLINE_SAMPLES + 1);
+        assert_eq!(keys[0], (0.0, [1.0]));
+        assert_eq!(keys[CUBICSPLINE_SAMPLES / 2], (0.5, [2.0]));
+        assert_eq!(keys[CUBICSPLINE_SAMPLES], (1.0, [3.0]));
+        // ease in: slower than linear at the start of the segment
+        assert!(keys[1].1[0] < 1.0 + 2.0 / CUBICSPLINE_SAMPLES as f32);
+
+        // a value per key instead of a triplet
+        let keys = linear_keys(
+            Interpolation::CubicSpline,
+            time.into_iter(),
+            [[1.0], [2.0], [4.0]].into_iter(),
+        );
+        assert!(keys.is_none());
+    }
+
+    #[test]
+    fn resample_keeps_steps() {
+        let id = [0.0, 0.0, 0.0, 1.0];
+        let frame = |time, x| Frame {
+            rotation: id,
+            translation: [x, 0.0, 0.0],
+            time,
+        };
+        let translations = [frame(0.0, 0.0), frame(1.0, 0.0), frame(1.0, 5.0)];
+        let rotations = [frame(0.0, 0.0), frame(0.5, 0.0), frame(2.0, 0.0)];
+        let frames = resample_channels(&translations, &rotations);
+        let keys: Vec<(f32, f32)> = frames.iter().map(|f| (f.time, f.translation[0])).collect();
+        assert_eq!(
+            keys,
+            [(0.0, 0.0), (0.5, 0.0), (1.0, 0.0), (1.0, 5.0), (2.0, 5.0)]
+        );
+    }
+}
@@ -0,0 +1,376 @@
+//! Vertex attributes the importer derives from the geometry when the glTF does not provide them.
+
+use bevy_mikktspace::Geometry;
+use gltf::mesh::Mode;
+use nalgebra::Vector3;
+use std::{collections::HashMap, str::FromStr};
+
+/// How normals are computed for glTF primitives without a `NORMAL` attribute.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NormalMode {
+    /// Area weighted average of the faces around a vertex. Faces whose normals differ by more than
+    /// the angle threshold do not contribute, their shared vertices are split.
+    Smooth,
+    /// Face normals. Vertices shared by faces that are not coplanar are split.
+    Flat,
+}
+
+impl FromStr for NormalMode {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.to_ascii_lowercase().as_str() {
+            "smooth" => Ok(NormalMode::Smooth),
+            "flat" => Ok(NormalMode::Flat),
+            _ => Err(format!(
+                "Invalid value for NormalMode: {}, Only smooth/flat are allowed value",
+                input
+            )),
+        }
+    }
+}
+
+/// Result of [`generate_normals`]. Vertex `i` of the new vertex list is a copy of the glTF vertex
+/// `vertex_source[i]`.
+pub(crate) struct GeneratedNormals {
+    pub normals: Vec<[f32; 3]>,
+    pub vertex_source: Vec<u32>,
+    pub indices: Vec<u32>,
+}
+
+/// Result of [`generate_tangents`]. Vertex `i` of the new vertex list is a copy of the input vertex
+/// `vertex_source[i]`.
+pub(crate) struct GeneratedTangents {
+    pub tangents: Vec<[f32; 4]>,
+    pub vertex_source: Vec<u32>,
+    pub indices: Vec<u32>,
+}
+
+/// Primitives that can be converted to a triangle list. Points and lines are not imported.
+pub(crate) fn has_triangles(primitive: &gltf::Primitive) -> bool {
+    matches!(
+        primitive.mode(),
+        Mode::Triangles | Mode::TriangleStrip | Mode::TriangleFan
+    )
+}
+
+/// Triangle list of a primitive with the winding order of the glTF. Non-indexed primitives use
+/// their vertices in order, degenerate strip and fan triangles are dropped.
+pub(crate) fn triangle_list(
+    mode: Mode,
+    indices: Option<Vec<u32>>,
+    vertex_count: usize,
+) -> Vec<u32> {
+    let indices = indices.unwrap_or_else(|| (0..vertex_count as u32).collect());
+    let n = indices.len();
+    let triangles: Vec<[u32; 3]> = match mode {
+        Mode::Triangles => indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .collect(),
+        Mode::TriangleStrip => (0..n.saturating_sub(2))
+            .map(|i| {
+                if i % 2 == 0 {
+                    [indices[i], indices[i + 1], indices[i + 2]]
+                } else {
+                    [indices[i], indices[i + 2], indices[i + 1]]
+                }
+            })
+            .filter(|t| t[0] != t[1] && t[1] != t[2] && t[0] != t[2])
+            .collect(),
+        Mode::TriangleFan => (0..n.saturating_sub(2))
+            .map(|i| [indices[i + 1], indices[i + 2], indices[0]])
+            .filter(|t| t[0] != t[1] && t[1] != t[2] && t[0] != t[2])
+            .collect(),
+        _ => Vec::new(),
+    };
+    triangles.concat()
+}
+
+/// Copies the values of a glTF attribute to the vertices split by [`generate_normals`] or
+/// [`generate_tangents`].
+pub(crate) fn remap<T: Copy>(values: Vec<T>, vertex_source: Option<&[u32]>) -> Vec<T> {
+    match vertex_source {
+        Some(source) if !values.is_empty() => source.iter().map(|&v| values[v as usize]).collect(),
+        _ => values,
+    }
+}
+
+pub(crate) fn generate_normals(
+    positions: &[[f32; 3]],
+    indices: &[u32],
+    mode: NormalMode,
+    angle: f32,
+) -> GeneratedNormals {
+    let point = |i: u32| Vector3::from(positions[i as usize]);
+    // the length of the cross product is twice the face area
+    let face_normals: Vec<Vector3<f32>> = indices
+        .chunks_exact(3)
+        .map(|f| (point(f[1]) - point(f[0])).cross(&(point(f[2]) - point(f[0]))))
+        .collect();
+    let unit = |n: &Vector3<f32>| n.try_normalize(f32::EPSILON).unwrap_or_else(Vector3::zeros);
+
+    // faces around every position, vertices split by the glTF (e.g. at uv seams) are still
+    // smoothed together
+    let position_key = |i: u32| positions[i as usize].map(f32::to_bits);
+    let mut adjacent: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+    if mode == NormalMode::Smooth {
+        for (face, f) in indices.chunks_exact(3).enumerate() {
+            for &v in f {
+                adjacent.entry(position_key(v)).or_default().push(face);
+            }
+        }
+    }
+    let min_cos = angle.to_radians().cos();
+
+    let mut generated = GeneratedNormals {
+        normals: Vec::new(),
+        vertex_source: Vec::new(),
+        indices: Vec::with_capacity(indices.len()),
+    };
+    let mut known: HashMap<(u32, [u32; 3]), u32> = HashMap::new();
+    for (face, f) in indices.chunks_exact(3).enumerate() {
+        let face_unit = unit(&face_normals[face]);
+        for &v in f {
+            let normal = match mode {
+                NormalMode::Flat => face_unit,
+                NormalMode::Smooth => {
+                    let sum: Vector3<f32> = adjacent[&position_key(v)]
+                        .iter()
+                        .filter(|&&other| unit(&face_normals[other]).dot(&face_unit) >= min_cos)
+                        .map(|&other| face_normals[other])
+                        .sum();
+                    unit(&sum)
+                }
+            };
+            let normal = if normal == Vector3::zeros() {
+                // degenerate faces
+                Vector3::z()
+            } else {
+                normal
+            };
+
+            let normal: [f32; 3] = normal.into();
+            let idx = *known
+                .entry((v, normal.map(f32::to_bits)))
+                .or_insert_with(|| {
+                    generated.normals.push(normal);
+                    generated.vertex_source.push(v);
+                    generated.normals.len() as u32 - 1
+                });
+            generated.indices.push(idx);
+        }
+    }
+    generated
+}
+
+/// Indexed triangle list of a single glTF primitive.
+struct TangentGeometry<'a> {
+    positions: &'a [[f32; 3]],
+    normals: &'a [[f32; 3]],
+    tex_coords: &'a [[f32; 2]],
+    indices: &'a [u32],
+    /// tangent of every face corner
+    tangents: Vec<[f32; 4]>,
+}
+
+impl TangentGeometry<'_> {
+    fn vertex(&self, face: usize, vert: usize) -> usize {
+        self.indices[face * 3 + vert] as usize
+    }
+}
+
+impl Geometry for TangentGeometry<'_> {
+    fn num_faces(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.positions[self.vertex(face, vert)]
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.normals[self.vertex(face, vert)]
+    }
+
+    /// MikkTSpace expects the texture origin at the bottom left, glTF puts it at the top left.
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        let [u, v] = self.tex_coords[self.vertex(face, vert)];
+        [u, 1.0 - v]
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.tangents[face * 3 + vert] = tangent;
+    }
+}
+
+/// MikkTSpace tangents in the glTF `TANGENT` layout (xyz, w = bitangent sign). A vertex shared by
+/// faces with different tangent frames, e.g. at mirrored uv seams, is split.
+pub(crate) fn generate_tangents(
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    tex_coords: &[[f32; 2]],
+    indices: &[u32],
+) -> Option<GeneratedTangents> {
+    let mut geometry = TangentGeometry {
+        positions,
+        normals,
+        tex_coords,
+        indices,
+        tangents: vec![[1.0, 0.0, 0.0, 1.0]; indices.len()],
+    };
+    if !bevy_mikktspace::generate_tangents(&mut geometry) {
+        return None;
+    }
+
+    let mut generated = GeneratedTangents {
+        tangents: Vec::new(),
+        vertex_source: Vec::new(),
+        indices: Vec::with_capacity(indices.len()),
+    };
+    let mut known: HashMap<(u32, [u32; 4]), u32> = HashMap::new();
+    for (&v, tangent) in indices.iter().zip(geometry.tangents) {
+        let idx = *known
+            .entry((v, tangent.map(f32::to_bits)))
+            .or_insert_with(|| {
+                generated.tangents.push(tangent);
+                generated.vertex_source.push(v);
+                generated.tangents.len() as u32 - 1
+            });
+        generated.indices.push(idx);
+    }
+    Some(generated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn strip_and_fan() {
+        let indices = vec![0, 1, 2, 3, 4];
+        assert_eq!(
+            triangle_list(Mode::TriangleStrip, Some(indices.clone()), 5),
+            vec![0, 1, 2, 1, 3, 2, 2, 3, 4]
+        );
+        assert_eq!(
+            triangle_list(Mode::TriangleFan, Some(indices), 5),
+            vec![1, 2, 0, 2, 3, 0, 3, 4, 0]
+        );
+        // degenerate triangles that join two strips
+        assert_eq!(
+            triangle_list(Mode::TriangleStrip, Some(vec![0, 1, 2, 2, 3, 3, 4, 5]), 6),
+            vec![0, 1, 2, 3, 5, 4]
+        );
+        // non-indexed, the incomplete last triangle is dropped
+        assert_eq!(
+            triangle_list(Mode::Triangles, None, 7),
+            vec![0, 1, 2, 3, 4, 5]
+        );
+    }
+
+    /// Two triangles folded by 90 degrees along the x axis.
+    const FOLD_POSITIONS: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0],
+        [0.0, 0.0, 1.0],
+    ];
+    const FOLD_INDICES: [u32; 6] = [0, 1, 2, 1, 0, 3];
+
+    #[test]
+    fn smooth_normals() {
+        let smooth = generate_normals(&FOLD_POSITIONS, &FOLD_INDICES, NormalMode::Smooth, 180.0);
+        assert_eq!(smooth.vertex_source, vec![0, 1, 2, 3]);
+        assert_eq!(smooth.indices, FOLD_INDICES.to_vec());
+        let diagonal = std::f32::consts::FRAC_1_SQRT_2;
+        assert_relative_eq!(smooth.normals[0][1], diagonal, epsilon = 1e-6);
+        assert_relative_eq!(smooth.normals[0][2], diagonal, epsilon = 1e-6);
+        assert_eq!(smooth.normals[2], [0.0, 0.0, 1.0]);
+        assert_eq!(smooth.normals[3], [0.0, 1.0, 0.0]);
+
+        // the fold is sharper than the threshold: the shared edge is split
+        let split = generate_normals(&FOLD_POSITIONS, &FOLD_INDICES, NormalMode::Smooth, 60.0);
+        assert_eq!(split.vertex_source, vec![0, 1, 2, 1, 0, 3]);
+        assert_eq!(split.indices, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(split.normals[0], [0.0, 0.0, 1.0]);
+        assert_eq!(split.normals[4], [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn flat_normals() {
+        let flat = generate_normals(&FOLD_POSITIONS, &FOLD_INDICES, NormalMode::Flat, 0.0);
+        assert_eq!(flat.vertex_source, vec![0, 1, 2, 1, 0, 3]);
+        assert_eq!(flat.normals[..3], [[0.0, 0.0, 1.0]; 3]);
+        assert_eq!(flat.normals[3..], [[0.0, 1.0, 0.0]; 3]);
+    }
+
+    #[test]
+    fn quad_tangents() {
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ];
+        let normals = [[0.0, 0.0, 1.0]; 4];
+        let indices = [0, 1, 2, 0, 2, 3];
+
+        // u along x, v along -y: the texture is upright
+        let tex_coords = [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]];
+        let generated = generate_tangents(&positions, &normals, &tex_coords, &indices).unwrap();
+        assert_eq!(generated.vertex_source, vec![0, 1, 2, 3]);
+        for t in generated.tangents {
+            assert_relative_eq!(t[0], 1.0, epsilon = 1e-6);
+            assert_relative_eq!(t[1], 0.0, epsilon = 1e-6);
+            assert_relative_eq!(t[3], 1.0);
+        }
+
+        // mirrored v flips the bitangent sign
+        let tex_coords = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        let generated = generate_tangents(&positions, &normals, &tex_coords, &indices).unwrap();
+        for t in generated.tangents {
+            assert_relative_eq!(t[0], 1.0, epsilon = 1e-6);
+            assert_relative_eq!(t[3], -1.0);
+        }
+    }
+
+    #[test]
+    fn mirrored_uv_tangents() {
+        // two quads sharing the edge 1-2, the uv of the right quad is mirrored at that edge
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [2.0, 0.0, 0.0],
+            [2.0, 1.0, 0.0],
+        ];
+        let normals = [[0.0, 0.0, 1.0]; 6];
+        let tex_coords = [
+            [0.0, 1.0],
+            [1.0, 1.0],
+            [1.0, 0.0],
+            [0.0, 0.0],
+            [0.0, 1.0],
+            [0.0, 0.0],
+        ];
+        let indices = [0, 1, 2, 0, 2, 3, 1, 4, 5, 1, 5, 2];
+
+        let generated = generate_tangents(&positions, &normals, &tex_coords, &indices).unwrap();
+        // the seam vertices are split
+        assert_eq!(generated.vertex_source, vec![0, 1, 2, 3, 1, 4, 5, 2]);
+        assert_eq!(generated.indices, vec![0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7]);
+        for (corner, &v) in generated.indices.iter().enumerate() {
+            let t = generated.tangents[v as usize];
+            let (x, sign) = if corner < 6 { (1.0, 1.0) } else { (-1.0, -1.0) };
+            assert_relative_eq!(t[0], x, epsilon = 1e-6);
+            assert_relative_eq!(t[3], sign);
+        }
+    }
+}
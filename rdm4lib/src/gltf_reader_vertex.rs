@@ -1,7 +1,7 @@
 use bytes::{BufMut, BytesMut};
 use half::f16;
 
-use crate::vertex::AnnoData;
+use crate::vertex::{AnnoData, IdentifierSize, VertexIdentifier};
 
 pub trait PutVertex<T, const I: u32, const N: usize> {
     fn put_vertex_data(&mut self, input: &AnnoData<T, I, N>);
@@ -20,3 +20,39 @@ impl<const I: u32, const N: usize> PutVertex<f16, I, N> for BytesMut {
         }
     }
 }
+
+pub trait PutComponent {
+    /// Encodes `values` as described by `ident`. Only the first `ident.count` values are used
+    /// for f16/f32 components.
+    fn put_component(&mut self, ident: &VertexIdentifier, values: [f32; 4]);
+}
+
+impl PutComponent for BytesMut {
+    fn put_component(&mut self, ident: &VertexIdentifier, values: [f32; 4]) {
+        match ident.unit_size {
+            IdentifierSize::U16 => {
+                for v in &values[..ident.count as usize] {
+                    self.put_u16_le(f16::from_f32(*v).to_bits());
+                }
+            }
+            IdentifierSize::F32 => {
+                for v in &values[..ident.count as usize] {
+                    self.put_f32_le(*v);
+                }
+            }
+            IdentifierSize::U32 => {
+                for v in values {
+                    let byte = match ident.interpretation {
+                        // [-1, 1]
+                        0x6 => (((v + 1.0) / 2.0) * 255.0).round() as u8,
+                        // integer (joint indices)
+                        0x0 => v as u8,
+                        // [0, 1] (weights, colours)
+                        _ => (v * 255.0).round() as u8,
+                    };
+                    self.put_u8(byte);
+                }
+            }
+        }
+    }
+}
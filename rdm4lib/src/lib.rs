@@ -3,8 +3,6 @@ use rdm_data_main::*;
 use rdm_data_main::{MeshInfo, RdmFile};
 use std::path::Path;
 
-use std::fs::File;
-
 use std::str;
 
 use half::f16;
@@ -17,32 +15,62 @@ extern crate log;
 #[macro_use]
 extern crate approx;
 
+pub mod dds;
 pub mod gltf_export;
 pub mod gltf_reader;
+mod gltf_reader_geometry;
 pub mod gltf_reader_vertex;
 pub mod rdm_anim;
 pub mod rdm_material;
+pub mod rdm_optimize;
 pub mod vertex;
 use crate::rdm_anim::RdAnim;
-use rdm_material::RdMaterial;
+use rdm_material::{RdMaterial, RdmMaterialInfo};
 
 use vertex::VertexFormat2;
 
 pub mod rdm_container;
 pub mod rdm_data_anim;
 pub mod rdm_data_main;
+pub mod rdm_error;
+use rdm_error::RdmError;
 
 pub struct RdModell {
     rdmf: Option<RdmFile<RdmKindMesh>>,
     pub mesh_info: Vec<MeshInfo>,
     pub joints: Option<Vec<RdJoint>>,
     pub triangle_indices: Vec<Triangle>,
+    pub bounding_box: BoundingBox,
+    pub materials: Vec<RdmMaterialInfo>,
+    pub export_info: RdExportInfo,
 
     pub vertex: VertexFormat2,
-    anim: Option<RdAnim>,
+    anims: Vec<RdAnim>,
     pub mat: Option<RdMaterial>,
 }
 
+/// Names stored in the header of an rdm mesh. They are informational only, e.g. the source file
+/// the asset was exported from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RdExportInfo {
+    /// Source file of the asset (`ExportInfo::export_name1`).
+    pub export_name1: String,
+    /// Export profile, e.g. `Anno5_Building_Skin_1Blend.rmp` (`ExportInfo::export_name2`).
+    pub export_name2: String,
+    /// Model name, referenced by the model's animations.
+    pub model_name: String,
+}
+
+impl Default for RdExportInfo {
+    fn default() -> Self {
+        RdExportInfo {
+            export_name1: DEFAULT_EXPORT_NAME.into(),
+            export_name2: DEFAULT_EXPORT_PROFILE.into(),
+            model_name: DEFAULT_MODEL_NAME.into(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RdJoint {
     name: String,
@@ -57,7 +85,11 @@ impl RdModell {
     }
 
     pub fn add_anim(&mut self, anim: RdAnim) {
-        self.anim = Some(anim);
+        self.anims.push(anim);
+    }
+
+    pub fn anims(&self) -> &[RdAnim] {
+        &self.anims
     }
 
     pub fn add_skin(&mut self) {
@@ -103,12 +135,20 @@ impl RdModell {
         self.joints = Some(joints_vec);
     }
 
-    fn new(buf: Vec<u8>) -> Self {
+    pub fn try_from_path<P: AsRef<Path>>(f_path: P) -> Result<Self, RdmError> {
+        let buffer = std::fs::read(&f_path)?;
+
+        info!("loaded {:?} into buffer", f_path.as_ref());
+        info!("buffer size: {}", buffer.len());
+        RdModell::try_from_bytes(buffer)
+    }
+
+    pub fn try_from_bytes(buf: Vec<u8>) -> Result<Self, RdmError> {
         let mut reader = std::io::Cursor::new(&buf);
 
-        let rdm: RdmFile<RdmKindMesh> = reader.read_le().unwrap();
+        let rdm: RdmFile<RdmKindMesh> = reader.read_le()?;
 
-        let vvert = VertexFormat2::read_format_via_data(&rdm);
+        let vvert = VertexFormat2::try_read_format_via_data(&rdm)?;
         info!(
             "Read {} vertices of type {} ({} bytes)",
             vvert.len(),
@@ -116,7 +156,19 @@ impl RdModell {
             vvert.get_size()
         );
 
+        let triangle_list = &rdm.header1.meta.triangle_list;
+        let part_size = triangle_list.info.part_size;
         let triangles_idx_count = rdm.header1.meta.triangle_list_len();
+        if !(part_size == 2 || part_size == 4) || !triangles_idx_count.is_multiple_of(3) {
+            return Err(RdmError::Parse {
+                pos: triangle_list.ptr.into(),
+                message: format!(
+                    "unexpected triangle list (part_size: {}, count: {})",
+                    part_size, triangles_idx_count
+                ),
+            });
+        }
+
         let triangles_real_count = triangles_idx_count / 3;
         let mut triangles = Vec::with_capacity(triangles_real_count as usize);
         let ts: Vec<_> = rdm.header1.meta.triangle_list().collect();
@@ -128,16 +180,22 @@ impl RdModell {
         }
 
         let mesh_info = rdm.header1.meta.0.mesh_info.iter().cloned().collect();
+        let bounding_box = rdm.header1.meta.bounding_box;
+        let materials = rdm.header1.try_materials()?;
+        let export_info = rdm.header1.try_export_info()?;
 
-        RdModell {
+        Ok(RdModell {
             rdmf: Some(rdm),
             mesh_info,
             joints: None,
             triangle_indices: triangles,
+            bounding_box,
+            materials,
+            export_info,
             vertex: vvert,
-            anim: None,
+            anims: Vec::new(),
             mat: None,
-        }
+        })
     }
 }
 
@@ -149,18 +207,7 @@ pub struct Triangle {
 
 impl<P: AsRef<Path>> From<P> for RdModell {
     fn from(f_path: P) -> Self {
-        let mut f = File::open(&f_path).unwrap();
-        let metadata = f.metadata().unwrap();
-        let len = metadata.len() as usize;
-        let mut buffer = vec![0; len];
-        std::io::Read::read_exact(&mut f, &mut buffer).expect("I/O ERROR");
-
-        let buffer_len = buffer.len();
-
-        info!("loaded {:?} into buffer", f_path.as_ref().to_str().unwrap());
-
-        info!("buffer size: {}", buffer_len);
-        RdModell::new(buffer)
+        RdModell::try_from_path(f_path).unwrap()
     }
 }
 
@@ -174,9 +221,40 @@ mod tests_intern {
         let bytes = include_bytes!("../rdm/fishery_others_cutout_lod0.rdm");
         let v = bytes.to_vec();
 
-        let rdm = RdModell::new(v);
+        let rdm = RdModell::try_from_bytes(v).unwrap();
         assert_eq!(rdm.vertex.len(), 32);
         assert_eq!(rdm.vertex.get_size(), 8);
         assert_eq!(rdm.triangle_indices.len() * 3, 78);
     }
+
+    #[test]
+    fn truncated_rdm_is_error() {
+        let bytes = include_bytes!("../rdm/fishery_others_cutout_lod0.rdm");
+        let v = bytes[..bytes.len() / 2].to_vec();
+
+        match RdModell::try_from_bytes(v) {
+            Err(RdmError::Parse { pos, .. }) => assert_ne!(pos, 0),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("truncated rdm must not load"),
+        }
+    }
+
+    #[test]
+    fn wrong_file_kind() {
+        let bytes = include_bytes!("../rdm/basalt_crusher_others_idle01.rdm");
+        assert!(matches!(
+            RdModell::try_from_bytes(bytes.to_vec()),
+            Err(RdmError::WrongFileKind {
+                expected: rdm_error::RdmFileKind::Mesh
+            })
+        ));
+
+        let bytes = include_bytes!("../rdm/fishery_others_cutout_lod0.rdm");
+        assert!(matches!(
+            RdAnim::try_from_bytes(bytes.to_vec(), "mesh".into()),
+            Err(RdmError::WrongFileKind {
+                expected: rdm_error::RdmFileKind::Anim
+            })
+        ));
+    }
 }
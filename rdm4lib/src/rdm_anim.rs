@@ -1,11 +1,11 @@
 use binrw::BinReaderExt;
-use std::path::Path;
+use std::{collections::HashMap, path::Path};
 
 use crate::{
     rdm_data_anim::Frame,
     rdm_data_main::{RdmFile, RdmKindAnim},
+    rdm_error::RdmError,
 };
-use std::fs::File;
 
 #[derive(Debug, Clone)]
 pub struct FrameCollection {
@@ -22,8 +22,26 @@ pub struct RdAnim {
 
 impl RdAnim {
     pub fn new(buffer: Vec<u8>, name_anim: String) -> Self {
+        RdAnim::try_from_bytes(buffer, name_anim).unwrap()
+    }
+
+    pub fn try_from_path<P: AsRef<Path>>(f_path: P) -> Result<Self, RdmError> {
+        let buffer = std::fs::read(&f_path)?;
+
+        info!("loaded {:?} into buffer", f_path.as_ref());
+        info!("buffer size: {}", buffer.len());
+
+        let name_anim = f_path
+            .as_ref()
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        RdAnim::try_from_bytes(buffer, name_anim)
+    }
+
+    pub fn try_from_bytes(buffer: Vec<u8>, name_anim: String) -> Result<Self, RdmError> {
         let mut reader = std::io::Cursor::new(&buffer);
-        let rdmm: RdmFile<RdmKindAnim> = reader.read_le().unwrap();
+        let rdmm: RdmFile<RdmKindAnim> = reader.read_le()?;
         let v = &rdmm.header1.meta_anim.anims;
 
         let time_max = rdmm.header1.meta_anim.time_max;
@@ -32,7 +50,14 @@ impl RdAnim {
         let mut anim_vec: Vec<FrameCollection> = Vec::with_capacity(v.len());
 
         for x in v.iter() {
-            let ent_model = x.j_name.as_ascii().into();
+            let ent_model = x
+                .j_name
+                .try_as_ascii()
+                .map_err(|e| RdmError::Parse {
+                    pos: x.j_name.ptr.into(),
+                    message: e.to_string(),
+                })?
+                .into();
             let ent = FrameCollection {
                 name: ent_model,
                 frames: x.j_data.storage.items.clone(),
@@ -40,27 +65,57 @@ impl RdAnim {
             anim_vec.push(ent);
         }
 
-        RdAnim {
+        Ok(RdAnim {
             anim_vec,
             name: name_anim,
             time_max,
+        })
+    }
+
+    /// Renames tracks so that they target joints with a different name, e.g. when an anim was
+    /// made for a skeleton with other bone names. Tracks not in `map` keep their name.
+    /// Only one track per joint is kept: a renamed track replaces a track that already had the
+    /// name, of several tracks renamed to the same joint the first one is kept.
+    pub fn remap_tracks(&mut self, map: &HashMap<String, String>) {
+        let mut renamed = vec![false; self.anim_vec.len()];
+        for (track, renamed) in self.anim_vec.iter_mut().zip(renamed.iter_mut()) {
+            if let Some(joint) = map.get(&track.name) {
+                debug!("remap track {} -> {}", track.name, joint);
+                track.name = joint.clone();
+                *renamed = true;
+            }
         }
+
+        let mut kept: HashMap<&str, usize> = HashMap::new();
+        for (i, track) in self.anim_vec.iter().enumerate() {
+            let k = kept.entry(&track.name).or_insert(i);
+            if renamed[i] && !renamed[*k] {
+                *k = i;
+            }
+        }
+        let kept: Vec<bool> = self
+            .anim_vec
+            .iter()
+            .enumerate()
+            .map(|(i, track)| kept[track.name.as_str()] == i)
+            .collect();
+
+        let mut i = 0;
+        self.anim_vec.retain(|track| {
+            i += 1;
+            if !kept[i - 1] {
+                warn!(
+                    "anim {}: dropping a second track of joint {}",
+                    self.name, track.name
+                );
+            }
+            kept[i - 1]
+        });
     }
 }
 
 impl<P: AsRef<Path>> From<P> for RdAnim {
     fn from(f_path: P) -> Self {
-        let mut f = File::open(&f_path).unwrap();
-        let mut buffer = Vec::new();
-        std::io::Read::read_to_end(&mut f, &mut buffer).ok();
-
-        let buffer_len = buffer.len();
-        info!("loaded {:?} into buffer", f_path.as_ref().to_str().unwrap());
-        info!("buffer size: {}", buffer_len);
-
-        RdAnim::new(
-            buffer,
-            String::from(f_path.as_ref().file_stem().unwrap().to_str().unwrap()),
-        )
+        RdAnim::try_from_path(f_path).unwrap()
     }
 }
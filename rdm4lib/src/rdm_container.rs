@@ -118,31 +118,30 @@ impl BinRead for RdmContainerPrefix {
         _args: Self::Args<'_>,
     ) -> binrw::BinResult<Self> {
         let c_prefix = RdmContainerPrefix {
-            count: <u32>::read_options(reader, endian, ()).unwrap(),
-            part_size: <u32>::read_options(reader, endian, ()).unwrap(),
+            count: <u32>::read_options(reader, endian, ())?,
+            part_size: <u32>::read_options(reader, endian, ())?,
         };
 
         if c_prefix.count == 0 {
             return Err(binrw::Error::AssertFail {
                 message: "count 0 ".into(),
-                pos: reader.stream_position().unwrap() - 8,
+                pos: reader.stream_position()? - 8,
             });
         }
 
         if c_prefix.part_size == 0 {
             return Err(binrw::Error::AssertFail {
                 message: "part_size 0 ".into(),
-                pos: reader.stream_position().unwrap() - 4,
+                pos: reader.stream_position()? - 4,
             });
         }
 
         let file_size = stream_len(reader)?;
-        if file_size
-            < (c_prefix.count * c_prefix.part_size) as u64 + reader.stream_position().unwrap()
-        {
+        let pos = reader.stream_position()?;
+        if file_size < c_prefix.count as u64 * c_prefix.part_size as u64 + pos {
             return Err(binrw::Error::AssertFail {
                 message: "RdmContainer > EOF".into(),
-                pos: reader.stream_position().unwrap() - 8,
+                pos: pos - 8,
             });
         }
 
@@ -209,10 +208,60 @@ where
     }
 }
 
+impl<T: RdmRead> RdmContainer<true, Fixed2, T> {
+    pub fn new(item: T) -> Self {
+        RdmContainer {
+            info: RdmContainerPrefix {
+                count: 1,
+                part_size: T::get_struct_byte_size() as u32,
+            },
+            storage: Vector1 { item: [item] },
+        }
+    }
+}
+
+impl<T: RdmRead> RdmContainer<true, Dynamic2, T> {
+    pub fn new(items: Vec<T>) -> Self {
+        RdmContainer {
+            info: RdmContainerPrefix {
+                count: items.len() as u32,
+                part_size: T::get_struct_byte_size() as u32,
+            },
+            storage: VectorN { items },
+        }
+    }
+}
+
+impl RdmContainer<false, Dynamic2, AnnoU8> {
+    /// `bytes.len()` must be a multiple of `part_size`.
+    pub fn from_bytes(part_size: u32, bytes: &[u8]) -> Self {
+        assert_eq!(bytes.len() % part_size as usize, 0);
+        RdmContainer {
+            info: RdmContainerPrefix {
+                count: bytes.len() as u32 / part_size,
+                part_size,
+            },
+            storage: VectorN {
+                items: bytes.iter().map(|x| AnnoU8(*x)).collect(),
+            },
+        }
+    }
+}
+
+impl From<&str> for RdmString {
+    fn from(s: &str) -> Self {
+        RdmString::new(s.bytes().map(AnnoChar).collect())
+    }
+}
+
 impl RdmString {
     pub fn as_ascii(&self) -> &str {
+        self.try_as_ascii().unwrap()
+    }
+
+    pub fn try_as_ascii(&self) -> Result<&str, str::Utf8Error> {
         let (_head, body, _tail) = unsafe { self.deref().align_to::<u8>() };
-        str::from_utf8(body).unwrap()
+        str::from_utf8(body)
     }
 }
 
@@ -233,6 +282,25 @@ pub type NullableAnnoPtr<T> = AnnoPtr2<true, T>;
 
 pub struct AnnoPtr2<const PTR_NULLABLE: bool, T>(pub FilePtr32<T>);
 
+impl<const PTR_NULLABLE: bool, T> AnnoPtr2<PTR_NULLABLE, T> {
+    /// The pointer value itself is only computed on write.
+    pub fn new(value: T) -> Self {
+        AnnoPtr2(FilePtr32 {
+            ptr: 0,
+            value: Some(value),
+        })
+    }
+}
+
+impl<T> NullableAnnoPtr<T> {
+    pub fn null() -> Self {
+        AnnoPtr2(FilePtr32 {
+            ptr: 0,
+            value: None,
+        })
+    }
+}
+
 impl<const PTR_NULLABLE: bool, T: BinRead> std::ops::Deref for AnnoPtr2<PTR_NULLABLE, T> {
     type Target = FilePtr32<T>;
 
@@ -272,15 +340,16 @@ where
         _args: Self::Args<'_>,
     ) -> binrw::BinResult<Self> {
         let mut p: FilePtr32<RdmContainer<N, C, T>> =
-            <_>::read_options(reader, endian, FilePtrArgs::default()).unwrap();
+            <_>::read_options(reader, endian, FilePtrArgs::default())?;
         let saved_ptr = p.ptr;
         if p.ptr != 0 {
             p.ptr = p.ptr.saturating_sub(8);
 
-            if p.ptr as u64 <= reader.stream_position().unwrap() {
+            let pos = reader.stream_position()?;
+            if p.ptr as u64 <= pos {
                 return Err(binrw::Error::AssertFail {
                     message: format!("unexpected back-pointer {:#x}", saved_ptr),
-                    pos: reader.stream_position().unwrap() - 4,
+                    pos: pos - 4,
                 });
             }
             let file_size = stream_len(reader)?;
@@ -288,7 +357,7 @@ where
             if file_size <= p.ptr.into() {
                 return Err(binrw::Error::AssertFail {
                     message: format!("out-of-bounds pointer {:#x}", saved_ptr),
-                    pos: reader.stream_position().unwrap() - 4,
+                    pos: pos - 4,
                 });
             }
 
@@ -301,10 +370,10 @@ where
                 value: None,
             }))
         } else {
-            return Err(binrw::Error::AssertFail {
+            Err(binrw::Error::AssertFail {
                 message: "null pointer".into(),
-                pos: reader.stream_position().unwrap() - 4,
-            });
+                pos: reader.stream_position()? - 4,
+            })
         }
     }
 }
@@ -5,15 +5,14 @@ use std::{
     path::PathBuf,
 };
 
-use crate::{rdm_anim::RdAnim, rdm_container::*};
+use crate::{rdm_anim::RdAnim, rdm_container::*, rdm_error::RdmError};
 use crate::{
-    rdm_data_main::{RdmFile, RdmKindAnim},
+    rdm_data_main::{ExportInfo, RdmFile, RdmKindAnim},
     RDMStructSizeTr,
 };
 
 #[binrw]
 #[bw(import_raw(end: &mut u64))]
-#[br(assert(_unknown0_15 == 15))]
 #[derive(RdmStructSize)]
 pub struct AnimMeta {
     #[bw(args_raw = end)]
@@ -47,43 +46,11 @@ pub struct Frame {
     pub time: f32,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use binrw::{BinReaderExt, BinWriterExt};
-    use std::fs;
-
-    #[test]
-    #[cfg(target_os = "linux")]
-    fn rdm_anim_serialisation_roundtrip() {
-        use crate::rdm_data_main::{RdmFile, RdmKindAnim};
-
-        let data = fs::read("rdm/basalt_crusher_others_work01.rdm").unwrap();
-        //let data = fs::read("rdm/basalt_crusher_others_idle01.rdm").unwrap();
-
-        let mut reader = std::io::Cursor::new(&data);
-        let rdm: RdmFile<RdmKindAnim> = reader.read_le().unwrap();
-
-        let mut dst = Vec::new();
-        let mut writer = std::io::Cursor::new(&mut dst);
-
-        writer
-            .write_type_args(&rdm, binrw::Endian::Little, ())
-            .unwrap();
+/// Value of `AnimMeta::_unknown0_15` in every known rdm anim. Other values are read and written
+/// unchanged.
+pub const ANIM_UNKNOWN0_15: u32 = 15;
 
-        let mut file = fs::File::create("/tmp/anim_out.rdm").unwrap();
-        std::io::Write::write_all(&mut file, &dst).unwrap();
-        assert_eq!(data, fs::read("/tmp/anim_out.rdm").unwrap())
-    }
-
-    #[test]
-    #[cfg(target_os = "linux")]
-    fn run_conv() {
-        let anim = RdAnim::from("rdm/basalt_crusher_others_work01.rdm");
-        let rdaw = RdAnimWriter2::new(anim);
-        rdaw.write_anim_rdm(Some("/tmp/".into()), false);
-    }
-}
+const EXPORT_PROFILE: &str = "Anno5_Building_Anim_UnCompressed.rmp";
 
 pub struct RdAnimWriter2 {
     name: String,
@@ -97,14 +64,14 @@ impl RdAnimWriter2 {
             let _ = fs::create_dir(&f);
             f
         });
+        // the anim name may contain dots, so the extension is appended instead of set
         if file.is_dir() {
-            file.push(self.name);
+            file.push(format!("{}.rdm", self.name));
         } else {
             let n = file.file_stem().unwrap();
-            let anim_name = format!("{}_{}", n.to_string_lossy(), self.name);
+            let anim_name = format!("{}_{}.rdm", n.to_string_lossy(), self.name);
             file.set_file_name(anim_name);
         }
-        file.set_extension("rdm");
 
         let mut writer = OpenOptions::new()
             .write(true)
@@ -119,80 +86,108 @@ impl RdAnimWriter2 {
             .unwrap();
     }
 
-    pub fn new(anim_input: RdAnim) -> Self {
-        use super::*;
+    /// `model_name` is the name of the mesh the animation belongs to and `export_name` is
+    /// stored as the source file name in the export info.
+    /// `unknown0_15` is 15 in every known rdm anim (see [`ANIM_UNKNOWN0_15`]).
+    /// An empty `model_name` is an error, the anim could not be read.
+    pub fn new(
+        anim_input: RdAnim,
+        model_name: &str,
+        export_name: &str,
+        unknown0_15: u32,
+    ) -> Result<Self, RdmError> {
+        if model_name.is_empty() {
+            return Err(RdmError::InvalidArgument("empty model name".into()));
+        }
+        info!("SEQUENCE EndTime (Max): {}", anim_input.time_max);
 
-        let data = include_bytes!("../rdm/basalt_crusher_others_idle01.rdm");
-        let mut reader = std::io::Cursor::new(&data);
-        let mut anim: RdmFile<RdmKindAnim> = reader.read_le().unwrap();
-
-        let export_name = br"G:\graphic\danny\Anno5\preproduction\buildings\others\basalt_crusher_others\scenes\basalt_crusher_others_idle01_01.max";
-
-        anim.header1.header2.export_name1.0 = binrw::FilePtr32 {
-            ptr: 0,
-            value: Some(RdmContainer {
-                info: RdmContainerPrefix {
-                    count: export_name.len() as u32,
-                    part_size: 1,
-                },
-                storage: rdm_container::VectorN {
-                    items: export_name.map(AnnoChar).into(),
-                },
-            }),
+        let anims: Vec<AnimInner> = anim_input
+            .anim_vec
+            .into_iter()
+            .map(|x| AnimInner {
+                j_name: AnnoPtr2::new(RdmString::from(x.name.as_str())),
+                j_data: AnnoPtr2::new(RdmTypedContainer::new(x.frames)),
+                _padding: [0; 16],
+            })
+            .collect();
+
+        let meta_anim = AnimMeta {
+            name: AnnoPtr2::new(RdmString::from(model_name)),
+            anims: AnnoPtr2::new(RdmTypedContainer::new(anims)),
+            time_max: anim_input.time_max,
+            _unknown0_15: unknown0_15,
+            _padding: [0; 32],
         };
 
-        let model_str: &[u8; 26] = br"basalt_crusher_others_lod0";
-        anim.header1.meta_anim.name.0 = binrw::FilePtr32 {
-            ptr: 0,
-            value: Some(RdmContainer {
-                info: RdmContainerPrefix {
-                    count: model_str.len() as u32,
-                    part_size: 1,
-                },
-                storage: rdm_container::VectorN {
-                    items: model_str.map(AnnoChar).into(),
-                },
-            }),
-        };
+        let export_info = ExportInfo::new(export_name, EXPORT_PROFILE);
 
-        anim.header1.meta_anim.time_max = anim_input.time_max;
-        info!("SEQUENCE EndTime (Max): {}", anim_input.time_max);
+        Ok(RdAnimWriter2 {
+            name: anim_input.name,
+            export: RdmFile::new_anim(export_info, meta_anim),
+        })
+    }
+}
 
-        let mut anim_data: Vec<AnimInner> = vec![];
-        for x in anim_input.anim_vec {
-            let o: AnimInner = AnimInner {
-                j_name: AnnoPtr2(binrw::FilePtr32 {
-                    ptr: 0,
-                    value: Some(RdmContainer {
-                        info: RdmContainerPrefix {
-                            count: x.name.as_bytes().len() as u32,
-                            part_size: 1,
-                        },
-                        storage: rdm_container::VectorN {
-                            items: x.name.as_bytes().iter().map(|c| AnnoChar(*c)).collect(),
-                        },
-                    }),
-                }),
-                j_data: AnnoPtr2(binrw::FilePtr32 {
-                    ptr: 0,
-                    value: Some(RdmContainer {
-                        info: RdmContainerPrefix {
-                            count: x.frames.len() as u32,
-                            part_size: 32,
-                        },
-                        storage: rdm_container::VectorN { items: x.frames },
-                    }),
-                }),
-                _padding: [0; 16],
-            };
-            anim_data.push(o);
-        }
-        anim.header1.meta_anim.anims.info.count = anim_data.len() as u32;
-        anim.header1.meta_anim.anims.storage.items = anim_data;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use binrw::{BinReaderExt, BinWriterExt};
+    use std::fs;
 
-        RdAnimWriter2 {
-            name: anim_input.name,
-            export: anim,
-        }
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn rdm_anim_serialisation_roundtrip() {
+        use crate::rdm_data_main::{RdmFile, RdmKindAnim};
+
+        let data = fs::read("rdm/basalt_crusher_others_work01.rdm").unwrap();
+        //let data = fs::read("rdm/basalt_crusher_others_idle01.rdm").unwrap();
+
+        let mut reader = std::io::Cursor::new(&data);
+        let rdm: RdmFile<RdmKindAnim> = reader.read_le().unwrap();
+
+        let mut dst = Vec::new();
+        let mut writer = std::io::Cursor::new(&mut dst);
+
+        writer
+            .write_type_args(&rdm, binrw::Endian::Little, ())
+            .unwrap();
+
+        let mut file = fs::File::create("/tmp/anim_out.rdm").unwrap();
+        std::io::Write::write_all(&mut file, &dst).unwrap();
+        assert_eq!(data, fs::read("/tmp/anim_out.rdm").unwrap())
+    }
+
+    #[test]
+    fn writer_rejects_empty_model_name() {
+        let bytes = include_bytes!("../rdm/basalt_crusher_others_work01.rdm");
+        let anim = RdAnim::try_from_bytes(bytes.to_vec(), "work01".into()).unwrap();
+        assert!(matches!(
+            RdAnimWriter2::new(anim, "", "rdm4", ANIM_UNKNOWN0_15),
+            Err(RdmError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn unknown0_15_roundtrip() {
+        let bytes = include_bytes!("../rdm/basalt_crusher_others_work01.rdm");
+        let anim = RdAnim::try_from_bytes(bytes.to_vec(), "work01".into()).unwrap();
+        let rdaw = RdAnimWriter2::new(anim, "basalt_crusher_others_lod2", "rdm4", 14).unwrap();
+
+        let mut dst = Vec::new();
+        std::io::Cursor::new(&mut dst)
+            .write_type_args(&rdaw.export, binrw::Endian::Little, ())
+            .unwrap();
+
+        let rdm: RdmFile<RdmKindAnim> = std::io::Cursor::new(&dst).read_le().unwrap();
+        assert_eq!(rdm.header1.meta_anim._unknown0_15, 14);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn run_conv() {
+        let anim = RdAnim::from("rdm/basalt_crusher_others_work01.rdm");
+        let rdaw = RdAnimWriter2::new(anim, "basalt_crusher_others_lod2", "rdm4", ANIM_UNKNOWN0_15)
+            .unwrap();
+        rdaw.write_anim_rdm(Some("/tmp/".into()), false);
     }
 }
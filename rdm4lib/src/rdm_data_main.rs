@@ -6,9 +6,16 @@ use std::{
 };
 
 use binrw::{binrw, BinWriterExt};
+use nalgebra::{Quaternion, UnitQuaternion, Vector3};
 use std::marker::PhantomData;
 
-use crate::{rdm_container::*, rdm_data_anim::AnimMeta, RdModell};
+use crate::{
+    rdm_container::*,
+    rdm_data_anim::AnimMeta,
+    rdm_error::{RdmError, RdmFileKind},
+    rdm_material::RdmMaterialInfo,
+    RdExportInfo, RdModell,
+};
 use rdm_derive::RdmStructSize;
 
 pub trait RDMStructSizeTr {
@@ -96,10 +103,37 @@ pub struct Meta {
     d: (),
 
     _padding_ff: u32, // 0x_FF_FF_FF_FF or 0x0
-    _unknown_box: [u8; 24],
+    pub bounding_box: BoundingBox,
     _padding_zero: [u8; 40],
 }
 
+/// Axis-aligned bounding box of all vertex positions (min xyz, max xyz).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[binrw]
+pub struct BoundingBox {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl BoundingBox {
+    pub fn from_points(points: impl Iterator<Item = [f32; 3]>) -> Self {
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        let mut empty = true;
+        for p in points {
+            for i in 0..3 {
+                min[i] = min[i].min(p[i]);
+                max[i] = max[i].max(p[i]);
+            }
+            empty = false;
+        }
+        if empty {
+            return Default::default();
+        }
+        BoundingBox { min, max }
+    }
+}
+
 impl Meta {
     pub fn triangle_list_len(&self) -> u32 {
         self.triangle_list.info.count
@@ -194,6 +228,62 @@ pub struct RdmHeader1 {
     _data: [u8; 48 - 5 * 4],
 }
 
+impl RdmHeader1 {
+    pub fn try_materials(&self) -> Result<Vec<RdmMaterialInfo>, RdmError> {
+        let mut materials = vec![];
+        if let Some(blobs) = self.rdm_blob_to_mat.value.as_ref() {
+            for blob in blobs.iter() {
+                materials.push(RdmMaterialInfo {
+                    name: try_string_or_empty(&blob.mat.name)?,
+                    texture_path: try_string_or_empty(&blob.mat.png)?,
+                });
+            }
+        }
+        Ok(materials)
+    }
+
+    pub fn try_export_info(&self) -> Result<RdExportInfo, RdmError> {
+        let (export_name1, export_name2) = match self.header2.value.as_ref() {
+            Some(info) => (
+                try_string_or_empty(&info.export_name1)?,
+                try_string_or_empty(&info.export_name2)?,
+            ),
+            None => Default::default(),
+        };
+        let model_name = match self.meta.value.as_ref() {
+            Some(meta) => try_string_or_empty(&meta.model_name.name)?,
+            None => String::new(),
+        };
+        Ok(RdExportInfo {
+            export_name1,
+            export_name2,
+            model_name,
+        })
+    }
+}
+
+/// A null pointer is read as empty string.
+fn try_string_or_empty(s: &NullableAnnoPtr<RdmString>) -> Result<String, RdmError> {
+    match s.value.as_ref() {
+        Some(v) => v
+            .try_as_ascii()
+            .map(String::from)
+            .map_err(|e| RdmError::Parse {
+                pos: s.ptr.into(),
+                message: e.to_string(),
+            }),
+        None => Ok(String::new()),
+    }
+}
+
+/// Empty rdm strings can not be read back, those are stored as null pointer instead.
+fn string_or_null(s: &str) -> NullableAnnoPtr<RdmString> {
+    match s {
+        "" => AnnoPtr2::null(),
+        s => AnnoPtr2::new(RdmString::from(s)),
+    }
+}
+
 #[binrw]
 #[bw(import_raw(end: &mut u64))]
 #[derive(RdmStructSize)]
@@ -205,6 +295,16 @@ pub struct ExportInfo {
     _data: [u8; 72 - 8],
 }
 
+impl ExportInfo {
+    pub fn new(export_name1: &str, export_name2: &str) -> Self {
+        ExportInfo {
+            export_name1: string_or_null(export_name1),
+            export_name2: string_or_null(export_name2),
+            _data: [0; 64],
+        }
+    }
+}
+
 pub struct RdmKindMesh;
 pub struct RdmKindAnim;
 pub trait RdmFileType {}
@@ -218,14 +318,30 @@ pub struct RdmFile<T: RdmFileType + 'static> {
     #[br(assert(header1.header2.ptr == 0x1C + header1.info.part_size))]
     // RdmHeader1 size is usually 48 but sometimes 52
     // TODO: fix some rdm anim's have a NULL meta_anim.ptr
-    #[br(assert(TypeId::of::<RdmKindMesh>() == TypeId::of::<T>() || header1.storage.item[0].meta_anim.ptr != 0 && header1.storage.item[0].meta.ptr == 0, "the input file is not a valid rdm anim!"))]
-    #[br(assert(TypeId::of::<RdmKindAnim>() == TypeId::of::<T>() || header1.storage.item[0].meta.ptr != 0 && header1.storage.item[0].meta_anim.ptr == 0, "the input file is not a rdm mesh!"))]
+    #[br(assert(TypeId::of::<RdmKindMesh>() == TypeId::of::<T>() || header1.storage.item[0].meta_anim.ptr != 0 && header1.storage.item[0].meta.ptr == 0, RdmError::WrongFileKind { expected: RdmFileKind::Anim }))]
+    #[br(assert(TypeId::of::<RdmKindAnim>() == TypeId::of::<T>() || header1.storage.item[0].meta.ptr != 0 && header1.storage.item[0].meta_anim.ptr == 0, RdmError::WrongFileKind { expected: RdmFileKind::Mesh }))]
     pub header1: RdmTypedT<RdmHeader1>,
 
     #[bw(ignore)]
     kind: PhantomData<T>,
 }
 
+impl RdmFile<RdmKindAnim> {
+    pub(crate) fn new_anim(export_info: ExportInfo, meta_anim: AnimMeta) -> Self {
+        RdmFile {
+            header1: RdmTypedT::new(RdmHeader1 {
+                header2: AnnoPtr2::new(RdmTypedT::new(export_info)),
+                meta: AnnoPtr2::null(),
+                rdm_blob_to_mat: AnnoPtr2::null(),
+                skin: AnnoPtr2::null(),
+                meta_anim: AnnoPtr2::new(RdmTypedT::new(meta_anim)),
+                _data: [0; 28],
+            }),
+            kind: PhantomData,
+        }
+    }
+}
+
 pub trait DataAndPointedToSize {
     fn get_direct_and_pointed_data_size(&self) -> u64;
 }
@@ -290,10 +406,191 @@ impl DataAndPointedToSize for AnnoU16 {
     }
 }
 
+pub struct RdWriter2 {
+    inner: RdmFile<RdmKindMesh>,
+}
+
+/// Default source file name stored in the export info of written rdm files.
+pub const DEFAULT_EXPORT_NAME: &str = "rdm4";
+/// Default export profile (`ExportInfo::export_name2`) of written rdm meshes.
+pub const DEFAULT_EXPORT_PROFILE: &str = "Anno5_Building_Skin_1Blend.rmp";
+/// Default model name of written rdm meshes. Animations reference the mesh by this name.
+pub const DEFAULT_MODEL_NAME: &str = "rdm4_model";
+/// File stem of the mesh written by [`RdWriter2::write_rdm`] into a directory.
+pub const MESH_FILE_STEM: &str = "out";
+
+impl RdWriter2 {
+    pub fn write_rdm(self, dir: Option<PathBuf>, create_new: bool) -> PathBuf {
+        let mut file = dir.unwrap_or_else(|| {
+            let f = PathBuf::from("rdm_out");
+            let _ = fs::create_dir(&f);
+            f
+        });
+        if file.is_dir() {
+            file.push(format!("{}.rdm", MESH_FILE_STEM));
+        }
+
+        let mut writer = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .create_new(create_new)
+            .open(&file)
+            .expect("I/O error");
+
+        writer
+            .write_type_args(&self.inner, binrw::Endian::Little, ())
+            .unwrap();
+
+        file.as_path().into()
+    }
+
+    pub fn new(rdm_in: RdModell) -> RdWriter2 {
+        let has_skin = rdm_in.has_skin();
+
+        let names = &rdm_in.export_info;
+        let export_info = ExportInfo::new(&names.export_name1, &names.export_name2);
+
+        let model_name = ModelName {
+            name: string_or_null(&names.model_name),
+            _padding: [0; 24],
+        };
+
+        // unknown maybe shader id
+        // 0: no anim
+        // 1: _Ib4
+        // 2:
+        // 3: I4b_W4b (eve)
+        // 4: I4b_W4b (other npc)
+        let format_identifiers = VertId {
+            rdm_container: AnnoPtr2::new(RdmTypedContainer::new(
+                rdm_in.vertex.identifiers.to_vec(),
+            )),
+            unknown_shader_id: if has_skin { 1 } else { 0 },
+            _padding: [0; 19],
+        };
+
+        let max_index = rdm_in
+            .triangle_indices
+            .iter()
+            .flat_map(|x| x.indices)
+            .max()
+            .unwrap_or(0);
+        info!("Max Triangle List Index: {}", max_index);
+        // 16-bit indices whenever possible, 32-bit for meshes with more than 65536 vertices.
+        let index_size = if max_index > u16::MAX.into() { 4 } else { 2 };
+        let mut indices = Vec::with_capacity(rdm_in.triangle_indices.len() * 3 * index_size);
+        for i in rdm_in.triangle_indices.iter().flat_map(|x| x.indices) {
+            match index_size {
+                2 => indices.extend_from_slice(&(i as u16).to_le_bytes()),
+                _ => indices.extend_from_slice(&i.to_le_bytes()),
+            }
+        }
+
+        let meta = Meta {
+            model_name: AnnoPtr2::new(RdmTypedT::new(model_name)),
+            format_identifiers: AnnoPtr2::new(RdmTypedT::new(format_identifiers)),
+            // _unknown is 3 in every known rdm mesh
+            unknown: AnnoPtr2::new(RdmTypedT::new(MetaUnknown {
+                _unknown: 3,
+                _padding: [0; 16],
+            })),
+            vertex: AnnoPtr2::new(RdmUntypedContainer::from_bytes(
+                rdm_in.vertex.get_size(),
+                rdm_in.vertex.as_bytes(),
+            )),
+            triangle_list: AnnoPtr2::new(RdmUntypedContainer::from_bytes(
+                index_size as u32,
+                &indices,
+            )),
+            mesh_info: AnnoPtr2::new(RdmTypedContainer::new(rdm_in.mesh_info.clone())),
+            _padding_ff: if has_skin { 0 } else { u32::MAX },
+            bounding_box: rdm_in.vertex.bounding_box(),
+            _padding_zero: [0; 40],
+        };
+
+        let mut mats = vec![];
+        for i in 0..MeshInfo::get_max_material(&rdm_in.mesh_info) + 1 {
+            let info = match rdm_in.materials.get(i as usize) {
+                Some(info) => info.clone(),
+                None => RdmMaterialInfo {
+                    name: format!("material_{}", i),
+                    texture_path: String::new(),
+                },
+            };
+            mats.push(RdmBlobToMat {
+                mat: AnnoPtr2::new(RdmTypedT::new(RdmMat {
+                    name: string_or_null(&info.name),
+                    png: string_or_null(&info.texture_path),
+                    _padding: [0; 40],
+                })),
+                _padding: Default::default(),
+            });
+        }
+
+        let skin = match rdm_in.joints {
+            Some(joints) => {
+                let mut raw_joints = vec![];
+                for j in &joints {
+                    let joint_quaternion = j.quaternion;
+
+                    let rx = joint_quaternion[0];
+                    let ry = joint_quaternion[1];
+                    let rz = joint_quaternion[2];
+                    let rw = joint_quaternion[3];
+
+                    let q = Quaternion::new(rw, rx, ry, rz);
+                    let unit_quaternion = UnitQuaternion::from_quaternion(q);
+
+                    let trans = j.transition;
+                    let tx = trans[0];
+                    let ty = trans[1];
+                    let tz = trans[2];
+                    let v: Vector3<f32> = Vector3::new(tx, ty, tz);
+
+                    // undo rotation since it will be applied on load
+                    // rdm -> internal representation -> rdm: v vector in add_skin should be equal to v_init
+                    let v_init = unit_quaternion.inverse_transform_vector(&v).scale(-1.0);
+                    let rot = unit_quaternion.quaternion().coords;
+
+                    raw_joints.push(RdmJoint {
+                        name: AnnoPtr2::new(RdmString::from(j.name.as_str())),
+                        t: [v_init.x, v_init.y, v_init.z],
+                        r: [rot.x, rot.y, rot.z, rot.w],
+                        parent_id: j.parent,
+                        _padding: [0; 48],
+                    });
+                }
+                AnnoPtr2::new(RdmTypedT::new(RdmBlobToJoint {
+                    joint: AnnoPtr2::new(RdmTypedContainer::new(raw_joints)),
+                    _padding: [0; 28],
+                }))
+            }
+            None => AnnoPtr2::null(),
+        };
+
+        let rdm = RdmFile {
+            header1: RdmTypedT::new(RdmHeader1 {
+                header2: AnnoPtr2::new(RdmTypedT::new(export_info)),
+                meta: AnnoPtr2::new(RdmTypedT::new(meta)),
+                rdm_blob_to_mat: AnnoPtr2::new(RdmTypedContainer::new(mats)),
+                skin,
+                meta_anim: AnnoPtr2::null(),
+                _data: [0; 28],
+            }),
+            kind: PhantomData,
+        };
+
+        RdWriter2 { inner: rdm }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{vertex::VertexFormat2, Triangle};
     use binrw::{BinReaderExt, BinWriterExt};
+    use bytes::Bytes;
     use std::fs;
 
     #[test]
@@ -339,271 +636,69 @@ mod tests {
         dbg!(data.len());
         assert_eq!(data, fs::read("/tmp/rdm_out.rdm").unwrap())
     }
-}
-
-pub struct RdWriter2 {
-    inner: RdmFile<RdmKindMesh>,
-}
 
-impl RdWriter2 {
-    pub fn write_rdm(self, dir: Option<PathBuf>, create_new: bool) -> PathBuf {
-        let mut file = dir.unwrap_or_else(|| {
-            let f = PathBuf::from("rdm_out");
-            let _ = fs::create_dir(&f);
-            f
-        });
-        if file.is_dir() {
-            file.push("out.rdm");
-        }
-
-        let mut writer = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .create_new(create_new)
-            .open(&file)
-            .expect("I/O error");
+    fn index_round_trip(rdm: RdModell, dst: &str, expected_part_size: u32) {
+        let expected: Vec<[u32; 3]> = rdm.triangle_indices.iter().map(|t| t.indices).collect();
 
-        writer
-            .write_type_args(&self.inner, binrw::Endian::Little, ())
-            .unwrap();
+        let file = RdWriter2::new(rdm).write_rdm(Some(dst.into()), false);
+        let rdm = RdModell::try_from_path(file).unwrap();
 
-        file.as_path().into()
+        let part_size = rdm
+            .rdmf
+            .as_ref()
+            .unwrap()
+            .header1
+            .meta
+            .triangle_list
+            .info
+            .part_size;
+        assert_eq!(part_size, expected_part_size);
+        let indices: Vec<[u32; 3]> = rdm.triangle_indices.iter().map(|t| t.indices).collect();
+        assert_eq!(indices, expected);
     }
 
-    pub fn new(rdm_in: RdModell) -> RdWriter2 {
-        use super::*;
-        // let data = fs::read("rdm/basalt_crusher_others_lod0.rdm").unwrap();
-        let data = include_bytes!("../rdm/basalt_crusher_others_lod0.rdm");
-
-        let mut reader = std::io::Cursor::new(&data);
-        let mut rdm: RdmFile<RdmKindMesh> = reader.read_le().unwrap();
-
-        let export_name = br"\\060.alpha\data\Art\graphic_backup\christian\#ANNO5\buildings\others\basalt_crusher_others\Lowpoly\basalt_crusher_others_low_05.max";
-
-        rdm.header1.header2.export_name1.0 = binrw::FilePtr32 {
-            ptr: 0,
-            value: Some(RdmContainer {
-                info: RdmContainerPrefix {
-                    count: export_name.len() as u32,
-                    part_size: 1,
-                },
-                storage: rdm_container::VectorN {
-                    items: export_name.map(AnnoChar).into(),
-                },
-            }),
-        };
-
-        // unknown maybe shader id
-        // 0: no anim
-        // 1: _Ib4
-        // 2:
-        // 3: I4b_W4b (eve)
-        // 4: I4b_W4b (other npc)
-        rdm.header1.meta.format_identifiers.unknown_shader_id =
-            if rdm_in.has_skin() { 1 } else { 0 };
-
-        rdm.header1.meta._padding_ff = if rdm_in.has_skin() {
-            Default::default()
-        } else {
-            u32::MAX
-        };
-
-        rdm.header1.meta.format_identifiers.rdm_container.0 = binrw::FilePtr32 {
-            ptr: 0,
-            value: Some(RdmContainer {
-                info: RdmContainerPrefix {
-                    count: rdm_in.vertex.identifiers.len() as u32,
-                    part_size: 16,
-                },
-                storage: rdm_container::VectorN {
-                    items: rdm_in.vertex.identifiers.clone().into(),
-                },
-            }),
-        };
-
-        rdm.header1.meta.mesh_info.0 = binrw::FilePtr32 {
-            ptr: 0,
-            value: Some(RdmContainer {
-                info: RdmContainerPrefix {
-                    count: rdm_in.mesh_info.len() as u32,
-                    part_size: 28,
-                },
-                storage: rdm_container::VectorN {
-                    items: rdm_in.mesh_info.clone(),
-                },
-            }),
-        };
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn write_u16_indices() {
+        let rdm = RdModell::try_from_path("rdm/fishery_others_cutout_lod0.rdm").unwrap();
+        index_round_trip(rdm, "/tmp/rdm_indices_u16.rdm", 2);
+    }
 
-        rdm.header1.meta._unknown_box = [
-            0x00, 0x80, 0xF8, 0xBF, 0x00, 0x40, 0x22, 0xC0, 0x00, 0x60, 0xF7, 0xBF, 0x00, 0x80,
-            0xFB, 0x3F, 0x00, 0xC0, 0xF2, 0x3F, 0x00, 0x80, 0xFC, 0x3F,
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn write_u32_indices() {
+        let src = RdModell::try_from_path("rdm/fishery_others_cutout_lod0.rdm").unwrap();
+        let vertex_count = 70000;
+        let vertex_size = src.vertex.get_size();
+        let vertex = VertexFormat2::new(
+            src.vertex.identifiers.clone(),
+            vertex_count,
+            vertex_size,
+            Bytes::from(vec![0; (vertex_count * vertex_size) as usize]),
+        );
+        let triangle_indices = vec![
+            Triangle { indices: [0, 1, 2] },
+            Triangle {
+                indices: [65535, 65536, vertex_count - 1],
+            },
         ];
-
-        rdm.header1.meta.triangle_list.0 = binrw::FilePtr32 {
-            ptr: 0,
-            value: Some(RdmContainer {
-                info: RdmContainerPrefix {
-                    count: rdm_in.triangle_indices.len() as u32 * 3,
-                    part_size: 2,
-                },
-                storage: rdm_container::VectorN {
-                    items: {
-                        let mut p = 0;
-                        let mut o = Vec::new();
-                        for x in &rdm_in.triangle_indices {
-                            o.push(AnnoU16(u16::try_from(x.indices[0]).unwrap()));
-                            o.push(AnnoU16(u16::try_from(x.indices[1]).unwrap()));
-                            o.push(AnnoU16(u16::try_from(x.indices[2]).unwrap()));
-                            p = p.max(x.indices[0]);
-                            p = p.max(x.indices[1]);
-                            p = p.max(x.indices[2]);
-                        }
-                        info!("Max Triangle List Index: {}", p);
-
-                        o.iter()
-                            .map(|x| x.0)
-                            .flat_map(u16::to_le_bytes)
-                            .map(AnnoU8)
-                            .collect()
-                    },
-                },
-            }),
-        };
-
-        rdm.header1.meta.vertex.0 = binrw::FilePtr32 {
-            ptr: 0,
-            value: Some(RdmContainer {
-                info: RdmContainerPrefix {
-                    count: rdm_in.vertex.len(),
-                    part_size: rdm_in.vertex.get_size(),
-                },
-                storage: rdm_container::VectorN {
-                    items: rdm_in
-                        .vertex
-                        .as_bytes()
-                        .iter()
-                        .map(|x| AnnoU8(*x))
-                        .collect::<Vec<_>>(),
-                },
-            }),
+        let rdm = RdModell {
+            rdmf: None,
+            mesh_info: vec![MeshInfo {
+                start_index_location: 0,
+                index_count: 6,
+                material: 0,
+                _padding: [0; 16],
+            }],
+            joints: None,
+            triangle_indices,
+            bounding_box: Default::default(),
+            materials: vec![],
+            export_info: Default::default(),
+            vertex,
+            anims: Vec::new(),
+            mat: None,
         };
-
-        let material = br"Default Standard12432142134";
-        let dummy_png_path = br"d:/projekte/anno5/game/testdata/graphics/dummy_objects/dummy_christian/rdm/basalt_crusher_others/diffuse.png";
-
-        let mut mats = vec![];
-        for _ in 0..MeshInfo::get_max_material(&rdm_in.mesh_info) + 1 {
-            let dummy_mat = RdmBlobToMat {
-                mat: AnnoPtr2(binrw::FilePtr32 {
-                    ptr: 0,
-                    value: Some(RdmContainer {
-                        info: RdmContainerPrefix {
-                            count: 1,
-                            part_size: 48,
-                        },
-                        storage: rdm_container::Vector1 {
-                            item: [RdmMat {
-                                name: AnnoPtr2(binrw::FilePtr32 {
-                                    ptr: 0,
-                                    value: Some(RdmContainer {
-                                        info: RdmContainerPrefix {
-                                            count: material.len() as u32,
-                                            part_size: 1,
-                                        },
-                                        storage: rdm_container::VectorN {
-                                            items: material.map(AnnoChar).into(),
-                                        },
-                                    }),
-                                }),
-                                png: AnnoPtr2(binrw::FilePtr32 {
-                                    ptr: 0,
-                                    value: Some(RdmContainer {
-                                        info: RdmContainerPrefix {
-                                            count: dummy_png_path.len() as u32,
-                                            part_size: 1,
-                                        },
-                                        storage: rdm_container::VectorN {
-                                            items: dummy_png_path.map(AnnoChar).into(),
-                                        },
-                                    }),
-                                }),
-                                _padding: [0; 40],
-                            }],
-                        },
-                    }),
-                }),
-                _padding: Default::default(),
-            };
-            mats.push(dummy_mat);
-        }
-
-        if rdm_in.has_skin() {
-            let mut replacement_raw_joints = vec![];
-            for j in &rdm_in.joints.unwrap() {
-                let joint_quaternion = j.quaternion;
-
-                let rx = joint_quaternion[0];
-                let ry = joint_quaternion[1];
-                let rz = joint_quaternion[2];
-                let rw = joint_quaternion[3];
-
-                let q = Quaternion::new(rw, rx, ry, rz);
-                let unit_quaternion = UnitQuaternion::from_quaternion(q);
-
-                let trans = j.transition;
-                let tx = trans[0];
-                let ty = trans[1];
-                let tz = trans[2];
-                let v: Vector3<f32> = Vector3::new(tx, ty, tz);
-
-                // undo rotation since it will be applied on load
-                // rdm -> internal representation -> rdm: v vector in add_skin should be equal to v_init
-                let v_init = unit_quaternion.inverse_transform_vector(&v).scale(-1.0);
-                let rot = unit_quaternion.quaternion().coords;
-
-                let res = RdmJoint {
-                    name: AnnoPtr2(binrw::FilePtr32 {
-                        ptr: 0,
-                        value: Some(RdmContainer {
-                            info: RdmContainerPrefix {
-                                count: j.name.as_bytes().len() as u32,
-                                part_size: 1,
-                            },
-                            storage: rdm_container::VectorN {
-                                items: j.name.as_bytes().iter().map(|c| AnnoChar(*c)).collect(),
-                            },
-                        }),
-                    }),
-                    t: [v_init.x, v_init.y, v_init.z],
-                    r: [rot.x, rot.y, rot.z, rot.w],
-                    parent_id: j.parent,
-                    _padding: [0; 48],
-                };
-
-                replacement_raw_joints.push(res);
-            }
-            assert_eq!(rdm.header1.skin.joint.info.part_size, 84);
-            rdm.header1.skin.joint.info.count = replacement_raw_joints.len() as u32;
-            rdm.header1.skin.joint.storage.items = replacement_raw_joints;
-        } else {
-            rdm.header1.skin.0 = binrw::FilePtr32 {
-                ptr: 0,
-                value: None,
-            }
-        }
-
-        rdm.header1.rdm_blob_to_mat.0 = binrw::FilePtr32 {
-            ptr: 0,
-            value: Some(RdmContainer {
-                info: RdmContainerPrefix {
-                    count: mats.len() as u32,
-                    part_size: 28,
-                },
-                storage: rdm_container::VectorN { items: mats },
-            }),
-        };
-
-        RdWriter2 { inner: rdm }
+        index_round_trip(rdm, "/tmp/rdm_indices_u32.rdm", 4);
     }
 }
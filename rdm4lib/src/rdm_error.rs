@@ -0,0 +1,106 @@
+use std::{error, fmt, io};
+
+/// The two kinds of rdm files this crate can read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RdmFileKind {
+    Mesh,
+    Anim,
+}
+
+impl fmt::Display for RdmFileKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RdmFileKind::Mesh => write!(f, "rdm mesh"),
+            RdmFileKind::Anim => write!(f, "rdm anim"),
+        }
+    }
+}
+
+/// Error returned by the fallible loading functions
+/// (`RdModell::try_from_path`, `RdAnim::try_from_bytes`, ...).
+#[derive(Debug)]
+pub enum RdmError {
+    Io(io::Error),
+    /// The input is not a well-formed rdm file. `pos` is the file offset of the structure that
+    /// could not be parsed.
+    Parse {
+        pos: u64,
+        message: String,
+    },
+    UnsupportedVertexLayout(String),
+    /// DDS pixel format that cannot be decoded.
+    UnsupportedTextureFormat(String),
+    /// Input that would write an rdm this crate cannot read back.
+    InvalidArgument(String),
+    /// e.g. an rdm anim was passed where an rdm mesh was expected.
+    WrongFileKind {
+        expected: RdmFileKind,
+    },
+}
+
+impl fmt::Display for RdmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RdmError::Io(e) => write!(f, "I/O error: {}", e),
+            RdmError::Parse { pos, message } => write!(f, "{} at {:#x}", message, pos),
+            RdmError::UnsupportedVertexLayout(layout) => {
+                write!(f, "unsupported vertex layout: {}", layout)
+            }
+            RdmError::UnsupportedTextureFormat(format) => {
+                write!(f, "unsupported texture format: {}", format)
+            }
+            RdmError::InvalidArgument(message) => write!(f, "invalid argument: {}", message),
+            RdmError::WrongFileKind { expected } => {
+                write!(f, "the input file is not a valid {}!", expected)
+            }
+        }
+    }
+}
+
+impl error::Error for RdmError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            RdmError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RdmError {
+    fn from(e: io::Error) -> Self {
+        RdmError::Io(e)
+    }
+}
+
+impl From<binrw::Error> for RdmError {
+    fn from(e: binrw::Error) -> Self {
+        if let Some(RdmError::WrongFileKind { expected }) = e.custom_err::<RdmError>() {
+            return RdmError::WrongFileKind {
+                expected: *expected,
+            };
+        }
+        match e {
+            binrw::Error::Backtrace(bt) => RdmError::from(*bt.error),
+            binrw::Error::Io(e) => RdmError::Io(e),
+            binrw::Error::AssertFail { pos, message } => RdmError::Parse { pos, message },
+            binrw::Error::BadMagic { pos, .. } => RdmError::Parse {
+                pos,
+                message: "bad magic".into(),
+            },
+            binrw::Error::Custom { pos, err } => RdmError::Parse {
+                pos,
+                message: err.to_string(),
+            },
+            binrw::Error::NoVariantMatch { pos } | binrw::Error::EnumErrors { pos, .. } => {
+                RdmError::Parse {
+                    pos,
+                    message: "unknown enum value".into(),
+                }
+            }
+            e => RdmError::Parse {
+                pos: 0,
+                message: e.to_string(),
+            },
+        }
+    }
+}
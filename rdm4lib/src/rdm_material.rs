@@ -1,8 +1,4 @@
-use std::process::Command;
-use std::{
-    fs,
-    path::{Path, PathBuf},
-};
+use std::path::{Path, PathBuf};
 
 #[derive(Clone, Debug)]
 pub struct RdMaterial {
@@ -19,47 +15,6 @@ impl RdMaterial {
             c_model_diff_tex: v,
         }
     }
-
-    pub fn run_dds_converter(&self, dst: &Path) {
-        if cfg!(windows) {
-            self.run_texconv(dst);
-        } else {
-            unimplemented!("DDS convert needs Windows texconv!");
-        }
-    }
-
-    fn run_texconv(&self, dst: &Path) {
-        warn!("running texconv ...");
-        for p in self.c_model_diff_tex.iter() {
-            let ab_path = p.canonicalize().unwrap();
-            let ab_dst = dst.canonicalize().unwrap();
-
-            let mut file_dst = ab_dst.join(ab_path.file_stem().unwrap());
-            file_dst.set_extension("PNG");
-            if file_dst.is_file() {
-                match fs::remove_file(&file_dst) {
-                    Ok(_) => {
-                        debug!("removed: {:?}", &file_dst)
-                    }
-                    Err(e) => {
-                        debug!("failed to remove: {:?}, {:?}", &file_dst, e)
-                    }
-                }
-            }
-
-            let output = Command::new("texconv.exe")
-                .arg(ab_path.as_os_str())
-                .arg(r"-o")
-                .arg(ab_dst.as_os_str())
-                .arg(r"-ft")
-                .arg(r"png")
-                .output()
-                .expect("failed to execute texconv.exe");
-            debug!("{:?}", &ab_path.to_str().unwrap()[4..]);
-            debug!("{:?}", &ab_dst.to_str().unwrap()[4..]);
-            debug!("{:?}", output);
-        }
-    }
 }
 
 impl<P: AsRef<Path>> From<P> for RdMaterial
@@ -79,3 +34,10 @@ impl<'a> IntoIterator for &'a RdMaterial {
         self.c_model_diff_tex.iter()
     }
 }
+
+/// Entry of the material table of an rdm mesh. `MeshInfo::material` is an index into this table.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RdmMaterialInfo {
+    pub name: String,
+    pub texture_path: String,
+}
@@ -0,0 +1,295 @@
+use crate::vertex::VertexFormat2;
+use crate::{RdModell, Triangle};
+use bytes::BytesMut;
+use std::collections::{HashMap, VecDeque};
+
+/// Size of the FIFO post-transform cache the triangles are ordered for and the ACMR is measured
+/// with.
+pub const CACHE_SIZE: usize = 16;
+
+/// Result of [`RdModell::optimize`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OptimizeReport {
+    pub vertices_before: u32,
+    pub vertices_after: u32,
+    /// Average cache miss ratio (transformed vertices per triangle) before the optimisation.
+    pub acmr_before: f32,
+    pub acmr_after: f32,
+}
+
+impl RdModell {
+    /// Welds vertices with identical packed data, reorders the triangles of every `MeshInfo` for
+    /// the post-transform vertex cache (Tipsify) and then the vertices in order of first use.
+    /// Unreferenced vertices are dropped. The mesh ranges and the winding are kept.
+    pub fn optimize(&mut self) -> OptimizeReport {
+        let vertices_before = self.vertex.len();
+        let mut indices: Vec<u32> = self
+            .triangle_indices
+            .iter()
+            .flat_map(|t| t.indices)
+            .collect();
+        let acmr_before = acmr(&indices, CACHE_SIZE);
+
+        let stride = self.vertex.get_size() as usize;
+        let bytes = self.vertex.as_bytes();
+
+        let mut unique: HashMap<&[u8], u32> = HashMap::new();
+        let welded: Vec<u32> = bytes
+            .chunks_exact(stride)
+            .enumerate()
+            .map(|(i, v)| *unique.entry(v).or_insert(i as u32))
+            .collect();
+        for i in indices.iter_mut() {
+            *i = welded[*i as usize];
+        }
+
+        for info in &self.mesh_info {
+            let start = info.start_index_location as usize;
+            let range = start..start + info.index_count as usize;
+            let ordered = tipsify(&indices[range.clone()], CACHE_SIZE);
+            indices[range].copy_from_slice(&ordered);
+        }
+
+        let mut remap = vec![u32::MAX; welded.len()];
+        let mut buffer = BytesMut::with_capacity(unique.len() * stride);
+        let mut vertex_count = 0;
+        for i in indices.iter_mut() {
+            let old = *i as usize;
+            if remap[old] == u32::MAX {
+                remap[old] = vertex_count;
+                vertex_count += 1;
+                buffer.extend_from_slice(&bytes[old * stride..(old + 1) * stride]);
+            }
+            *i = remap[old];
+        }
+
+        let mut vertex = VertexFormat2::new(
+            self.vertex.identifiers.clone(),
+            vertex_count,
+            stride as u32,
+            buffer.freeze(),
+        );
+        if self.vertex.weight_sum.is_some() {
+            vertex.set_weight_sum();
+        }
+        self.vertex = vertex;
+        self.triangle_indices = indices
+            .chunks_exact(3)
+            .map(|t| Triangle {
+                indices: [t[0], t[1], t[2]],
+            })
+            .collect();
+
+        let report = OptimizeReport {
+            vertices_before,
+            vertices_after: vertex_count,
+            acmr_before,
+            acmr_after: acmr(&indices, CACHE_SIZE),
+        };
+        debug!("{:?}", report);
+        report
+    }
+}
+
+/// Average cache miss ratio of a triangle list with a FIFO cache of `cache_size` vertices.
+pub fn acmr(indices: &[u32], cache_size: usize) -> f32 {
+    let triangles = indices.len() / 3;
+    if triangles == 0 {
+        return 0.0;
+    }
+    let mut cache = VecDeque::with_capacity(cache_size + 1);
+    let mut misses = 0;
+    for &i in indices {
+        if !cache.contains(&i) {
+            misses += 1;
+            cache.push_back(i);
+            if cache.len() > cache_size {
+                cache.pop_front();
+            }
+        }
+    }
+    misses as f32 / triangles as f32
+}
+
+/// Triangle order of "Fast Triangle Reordering for Vertex Locality and Reduced Overdraw"
+/// (Sander, Nehab, Barczak 2007): fans around the vertex that will still be in the cache
+/// after its remaining triangles are emitted.
+fn tipsify(indices: &[u32], cache_size: usize) -> Vec<u32> {
+    let mut local: HashMap<u32, usize> = HashMap::new();
+    let mut global = Vec::new();
+    let triangles: Vec<[usize; 3]> = indices
+        .chunks_exact(3)
+        .map(|t| {
+            let mut tri = [0; 3];
+            for (dst, &v) in tri.iter_mut().zip(t) {
+                *dst = *local.entry(v).or_insert_with(|| {
+                    global.push(v);
+                    global.len() - 1
+                });
+            }
+            tri
+        })
+        .collect();
+    let n = global.len();
+
+    // triangles of every vertex, live counts the ones not emitted yet
+    let mut live = vec![0usize; n];
+    for t in &triangles {
+        for &v in t {
+            live[v] += 1;
+        }
+    }
+    let mut offsets = vec![0; n + 1];
+    for v in 0..n {
+        offsets[v + 1] = offsets[v] + live[v];
+    }
+    let mut adjacency = vec![0; offsets[n]];
+    let mut fill = offsets.clone();
+    for (t, tri) in triangles.iter().enumerate() {
+        for &v in tri {
+            adjacency[fill[v]] = t;
+            fill[v] += 1;
+        }
+    }
+
+    let mut cache_time = vec![0; n];
+    let mut emitted = vec![false; triangles.len()];
+    let mut dead_end = Vec::new();
+    let mut time = cache_size + 1;
+    let mut cursor = 0;
+    let mut out = Vec::with_capacity(indices.len());
+
+    let mut fanning = if n > 0 { Some(0) } else { None };
+    while let Some(f) = fanning {
+        let mut candidates = Vec::new();
+        for &t in &adjacency[offsets[f]..offsets[f + 1]] {
+            if emitted[t] {
+                continue;
+            }
+            emitted[t] = true;
+            for &v in &triangles[t] {
+                out.push(global[v]);
+                dead_end.push(v);
+                candidates.push(v);
+                live[v] -= 1;
+                if time - cache_time[v] > cache_size {
+                    cache_time[v] = time;
+                    time += 1;
+                }
+            }
+        }
+
+        // prefer the oldest candidate whose triangles still fit into the cache
+        let mut best = None;
+        let mut best_priority = 0;
+        for &v in &candidates {
+            if live[v] == 0 {
+                continue;
+            }
+            let age = time - cache_time[v];
+            let priority = if age + 2 * live[v] <= cache_size {
+                age
+            } else {
+                0
+            };
+            if best.is_none() || priority > best_priority {
+                best = Some(v);
+                best_priority = priority;
+            }
+        }
+        fanning = best.or_else(|| {
+            while let Some(v) = dead_end.pop() {
+                if live[v] > 0 {
+                    return Some(v);
+                }
+            }
+            while cursor < n {
+                cursor += 1;
+                if live[cursor - 1] > 0 {
+                    return Some(cursor - 1);
+                }
+            }
+            None
+        });
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Triangles of a `size` x `size` quad grid, row by row.
+    fn grid(size: u32) -> Vec<u32> {
+        let mut indices = Vec::new();
+        for y in 0..size {
+            for x in 0..size {
+                let v = y * (size + 1) + x;
+                indices.extend_from_slice(&[v, v + 1, v + size + 1]);
+                indices.extend_from_slice(&[v + 1, v + size + 2, v + size + 1]);
+            }
+        }
+        indices
+    }
+
+    #[test]
+    fn tipsify_grid() {
+        let indices = grid(32);
+        let ordered = tipsify(&indices, CACHE_SIZE);
+
+        let mut before: Vec<&[u32]> = indices.chunks(3).collect();
+        let mut after: Vec<&[u32]> = ordered.chunks(3).collect();
+        before.sort();
+        after.sort();
+        assert_eq!(before, after);
+
+        let acmr_before = acmr(&indices, CACHE_SIZE);
+        let acmr_after = acmr(&ordered, CACHE_SIZE);
+        assert!(acmr_before > 1.0, "{}", acmr_before);
+        assert!(acmr_after < 0.8, "{}", acmr_after);
+    }
+
+    /// Vertex data of the triangles of every mesh, in a comparable order.
+    fn mesh_triangles(rdm: &RdModell) -> Vec<Vec<Vec<u8>>> {
+        let stride = rdm.vertex.get_size() as usize;
+        let bytes = rdm.vertex.as_bytes();
+        rdm.mesh_info
+            .iter()
+            .map(|info| {
+                let start = info.start_index_location as usize / 3;
+                let end = start + info.index_count as usize / 3;
+                let mut triangles: Vec<Vec<u8>> = rdm.triangle_indices[start..end]
+                    .iter()
+                    .map(|t| {
+                        t.indices
+                            .iter()
+                            .flat_map(|&i| &bytes[i as usize * stride..(i as usize + 1) * stride])
+                            .copied()
+                            .collect()
+                    })
+                    .collect();
+                triangles.sort();
+                triangles
+            })
+            .collect()
+    }
+
+    #[test]
+    fn optimize_keeps_meshes() {
+        let bytes = include_bytes!("../rdm/fishery_others_lod2.rdm");
+        let mut rdm = RdModell::try_from_bytes(bytes.to_vec()).unwrap();
+        let before = mesh_triangles(&rdm);
+
+        let report = rdm.optimize();
+        assert_eq!(mesh_triangles(&rdm), before);
+        assert_eq!(rdm.vertex.len(), report.vertices_after);
+        assert!(report.vertices_after <= report.vertices_before);
+        assert!(report.acmr_after < report.acmr_before, "{:?}", report);
+    }
+
+    #[test]
+    fn acmr_single_quad() {
+        assert_eq!(acmr(&[0, 1, 2, 2, 1, 3], CACHE_SIZE), 2.0);
+        assert_eq!(acmr(&[], CACHE_SIZE), 0.0);
+    }
+}
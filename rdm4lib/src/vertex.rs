@@ -6,7 +6,7 @@ use crate::{rdm_data_main::RdmFile, *};
 use binrw::binrw;
 
 #[repr(C)]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 #[binrw]
 #[bw(import_raw(end: &mut u64))]
 #[derive(RdmStructSize)]
@@ -22,6 +22,7 @@ impl fmt::Display for VertexIdentifier {
         // TODO: cleanup
         let tmp = format!("{:?}", &self.uniq);
         let unit_size = match self.unit_size {
+            IdentifierSize::U32 if self.interpretation == 0x4 => 'c',
             IdentifierSize::U32 => 'b',
             IdentifierSize::U16 => 'h',
             IdentifierSize::F32 => 'f',
@@ -108,11 +109,11 @@ pub(crate) type N3f = AnnoData<f32, { UniqueIdentifier::Normal as u32 }, 3>;
 pub(crate) type N4b = AnnoData<u8, { UniqueIdentifier::Normal as u32 }, 4>;
 pub(crate) type G3f = AnnoData<f32, { UniqueIdentifier::GTangent as u32 }, 3>;
 pub(crate) type G4b = AnnoData<u8, { UniqueIdentifier::GTangent as u32 }, 4>;
-#[allow(dead_code)]
 pub(crate) type B3f = AnnoData<f32, { UniqueIdentifier::Bitangent as u32 }, 3>;
 pub(crate) type B4b = AnnoData<u8, { UniqueIdentifier::Bitangent as u32 }, 4>;
 pub(crate) type T2f = AnnoData<f32, { UniqueIdentifier::Texcoord as u32 }, 2>;
 pub(crate) type T2h = AnnoData<f16, { UniqueIdentifier::Texcoord as u32 }, 2>;
+pub(crate) type C4c = AnnoData<u8, { UniqueIdentifier::C4c as u32 }, 4>;
 pub(crate) type I4b = AnnoData<u8, { UniqueIdentifier::I4b as u32 }, 4>;
 pub(crate) type W4b = AnnoData<u8, { UniqueIdentifier::W4b as u32 }, 4>;
 
@@ -261,24 +262,48 @@ impl VertexFormat2 {
     }
 
     pub fn read_format_via_data(rdm: &RdmFile<RdmKindMesh>) -> Self {
+        Self::try_read_format_via_data(rdm).unwrap()
+    }
+
+    pub fn try_read_format_via_data(rdm: &RdmFile<RdmKindMesh>) -> Result<Self, RdmError> {
         let meta: &rdm_data_main::Meta = &rdm.header1.meta.0;
         let format_identifiers = &meta.format_identifiers;
 
         let ids = &format_identifiers.rdm_container;
-        assert_eq!(ids.info.part_size, 16);
+        if ids.info.part_size != 16 {
+            return Err(RdmError::UnsupportedVertexLayout(format!(
+                "identifier size {} (expected 16)",
+                ids.info.part_size
+            )));
+        }
 
         let vec: Vec<VertexIdentifier> = ids.iter().cloned().collect();
+        if let Some(unknown) = vec.iter().find(|x| x.uniq == UniqueIdentifier::Invalid) {
+            return Err(RdmError::UnsupportedVertexLayout(format!(
+                "unknown vertex identifier {:?}",
+                unknown
+            )));
+        }
 
         let vertex_count = meta.vertex.info.count;
         let vertex_size = meta.vertex.info.part_size;
+        let identifiers_size: u32 = vec.iter().map(|x| x.get_size()).sum();
+        if identifiers_size != vertex_size {
+            return Err(RdmError::UnsupportedVertexLayout(format!(
+                "{} identifiers describe {} bytes but the vertex size is {}",
+                vec.len(),
+                identifiers_size,
+                vertex_size
+            )));
+        }
         let vertex_buffer = Bytes::from(meta.vertex.iter().map(|x| x.0).collect::<Vec<u8>>());
 
-        Self::new(
+        Ok(Self::new(
             vec.into_boxed_slice(),
             vertex_count,
             vertex_size,
             vertex_buffer,
-        )
+        ))
     }
 
     #[allow(clippy::needless_lifetimes)]
@@ -360,6 +385,14 @@ impl VertexFormat2 {
         Some(it)
     }
 
+    /// Bounding box of the (already quantised) vertex positions.
+    pub fn bounding_box(&self) -> BoundingBox {
+        match self.iter::<P4h, P3f>(0) {
+            Some(iter) => BoundingBox::from_points(iter.map(|p| p.data)),
+            None => Default::default(),
+        }
+    }
+
     pub fn w4b_default_iter(&self) -> impl Iterator<Item = W4b> + '_ {
         std::iter::from_fn(|| {
             Some(W4b {
@@ -444,6 +477,16 @@ impl VertexIdentifier {
         }
     }
 
+    /// Vertex colour, one byte per channel (RGBA).
+    pub const fn c4c() -> Self {
+        VertexIdentifier {
+            uniq: UniqueIdentifier::C4c,
+            unit_size: IdentifierSize::U32,
+            interpretation: 0x4,
+            count: 1,
+        }
+    }
+
     pub const fn i4b() -> Self {
         VertexIdentifier {
             uniq: UniqueIdentifier::I4b,
@@ -496,23 +539,137 @@ pub const fn p4h_n4b_g4b_b4b_t2h() -> [VertexIdentifier; 5] {
     ]
 }
 
+pub const fn p4h_n4b_g4b_b4b_t2h_c4c() -> [VertexIdentifier; 6] {
+    [
+        VertexIdentifier::p4h(),
+        VertexIdentifier::n4b(),
+        VertexIdentifier::g4b(),
+        VertexIdentifier::b4b(),
+        VertexIdentifier::t2h(),
+        VertexIdentifier::c4c(),
+    ]
+}
+
+impl FromStr for VertexIdentifier {
+    type Err = String;
+
+    /// Parses a single component of a cfg `VertexFormat` string, e.g. `P4h`, `N4b` or `C4c`.
+    fn from_str(input: &str) -> Result<VertexIdentifier, Self::Err> {
+        let err = || format!("Invalid vertex component: {}", input);
+        let mut chars = input.chars();
+        let (kind, n, ty) = match (chars.next(), chars.next(), chars.next(), chars.next()) {
+            (Some(kind), Some(n), Some(ty), None) => (kind, n.to_digit(10).ok_or_else(err)?, ty),
+            _ => return Err(err()),
+        };
+
+        let uniq = match kind {
+            'P' => UniqueIdentifier::Position,
+            'N' => UniqueIdentifier::Normal,
+            'G' => UniqueIdentifier::GTangent,
+            'B' => UniqueIdentifier::Bitangent,
+            'T' => UniqueIdentifier::Texcoord,
+            'C' => UniqueIdentifier::C4c,
+            'I' => UniqueIdentifier::I4b,
+            'W' => UniqueIdentifier::W4b,
+            _ => return Err(err()),
+        };
+
+        // the sizes the glTF import can write and the rdm reader can read
+        let supported = match uniq {
+            UniqueIdentifier::Position => matches!((n, ty), (4, 'h') | (3, 'f')),
+            UniqueIdentifier::Normal | UniqueIdentifier::GTangent | UniqueIdentifier::Bitangent => {
+                matches!((n, ty), (4, 'b') | (3, 'f'))
+            }
+            UniqueIdentifier::Texcoord => matches!((n, ty), (2, 'h') | (2, 'f')),
+            UniqueIdentifier::C4c => (n, ty) == (4, 'c'),
+            _ => (n, ty) == (4, 'b'),
+        };
+        if !supported {
+            return Err(format!("Unsupported vertex component: {}", input));
+        }
+
+        let (unit_size, interpretation, count) = match ty {
+            // four bytes packed into one unit
+            'c' => (IdentifierSize::U32, 0x4, 1),
+            'b' => {
+                let interpretation = match uniq {
+                    UniqueIdentifier::Normal
+                    | UniqueIdentifier::GTangent
+                    | UniqueIdentifier::Bitangent => 0x6,
+                    UniqueIdentifier::W4b => 0x2,
+                    _ => 0x0,
+                };
+                (IdentifierSize::U32, interpretation, 1)
+            }
+            'h' => (IdentifierSize::U16, 0x0, n),
+            _ => (IdentifierSize::F32, 0x0, n),
+        };
+
+        Ok(VertexIdentifier {
+            uniq,
+            unit_size,
+            interpretation,
+            count,
+        })
+    }
+}
+
+/// Vertex layout of a written rdm mesh. Parsed from a cfg `VertexFormat` string
+/// like `P4h_N4b_G4b_B4b_T2h_I4b`.
 #[derive(Debug, Clone, Eq, PartialEq)]
-#[allow(non_camel_case_types)]
-pub enum TargetVertexFormat {
-    P4h_N4b_G4b_B4b_T2h,
-    P4h_N4b_G4b_B4b_T2h_I4b,
-    P4h_N4b_G4b_B4b_T2h_I4b_W4b,
+pub struct TargetVertexFormat {
+    pub identifiers: Box<[VertexIdentifier]>,
+}
+
+impl TargetVertexFormat {
+    pub fn contains(&self, uniq: UniqueIdentifier) -> bool {
+        self.identifiers.iter().any(|x| x.uniq == uniq)
+    }
+
+    /// Size of one vertex in bytes.
+    pub fn get_size(&self) -> u32 {
+        self.identifiers.iter().map(|x| x.get_size()).sum()
+    }
 }
+
+impl fmt::Display for TargetVertexFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text: Vec<String> = self.identifiers.iter().map(|x| x.to_string()).collect();
+        write!(f, "{}", text.join("_"))
+    }
+}
+
 impl FromStr for TargetVertexFormat {
     type Err = String;
 
     fn from_str(input: &str) -> Result<TargetVertexFormat, Self::Err> {
-        match input {
-            "P4h_N4b_G4b_B4b_T2h" => Ok(TargetVertexFormat::P4h_N4b_G4b_B4b_T2h),
-            "P4h_N4b_G4b_B4b_T2h_I4b" => Ok(TargetVertexFormat::P4h_N4b_G4b_B4b_T2h_I4b),
-            "P4h_N4b_G4b_B4b_T2h_I4b_W4b" => Ok(TargetVertexFormat::P4h_N4b_G4b_B4b_T2h_I4b_W4b),
-            _ => Err(format!("Invalid value for VertexFormat: {}", input)),
+        let identifiers = input
+            .split('_')
+            .map(VertexIdentifier::from_str)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Invalid value for VertexFormat {}: {}", input, e))?;
+        // at most four sets of a component can be read back
+        if let Some(repeated) = identifiers
+            .iter()
+            .find(|a| identifiers.iter().filter(|b| b.uniq == a.uniq).count() > 4)
+        {
+            return Err(format!(
+                "Invalid value for VertexFormat {}: more than 4 {}",
+                input, repeated
+            ));
+        }
+        if !identifiers
+            .iter()
+            .any(|x| x.uniq == UniqueIdentifier::Position)
+        {
+            return Err(format!(
+                "Invalid value for VertexFormat {}: no position",
+                input
+            ));
         }
+        Ok(TargetVertexFormat {
+            identifiers: identifiers.into_boxed_slice(),
+        })
     }
 }
 
@@ -552,7 +709,6 @@ mod tests_vertex {
             0x00, 0x00,
         ];
 
-        #[allow(dead_code)]
         const C4C_IDENTIFIER: [u8; 16] = [
             0x05, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00,
             0x00, 0x00,
@@ -582,5 +738,53 @@ mod tests_vertex {
         for i in 0..6 {
             assert_eq!(&bytes2[i], &bytes[i]);
         }
+
+        let c = [VertexIdentifier::c4c()];
+        let bytes = unsafe { c.align_to::<[u8; 16]>().1 };
+        assert_eq!(bytes[0], C4C_IDENTIFIER);
+        assert_eq!(VertexIdentifier::c4c().to_string(), "C4c");
+    }
+
+    #[test]
+    fn parse_vertex_format() {
+        let f: TargetVertexFormat = "P4h_N4b_G4b_B4b_T2h_I4b".parse().unwrap();
+        assert_eq!(&*f.identifiers, &p4h_n4b_g4b_b4b_t2h_i4b()[..]);
+        let f: TargetVertexFormat = "P4h_N4b_G4b_B4b_T2h_C4c".parse().unwrap();
+        assert_eq!(&*f.identifiers, &p4h_n4b_g4b_b4b_t2h_c4c()[..]);
+
+        for s in [
+            "P4h_N4b_G4b_B4b_T2h_I4b_I4b_W4b_W4b",
+            "P4h_N4b_G4b_B4b_T2h_T2h",
+            "P3f_N4b_T2f",
+        ] {
+            let f: TargetVertexFormat = s.parse().unwrap();
+            assert_eq!(f.to_string(), s);
+        }
+        let f: TargetVertexFormat = "P3f_N4b_T2f".parse().unwrap();
+        assert_eq!(f.get_size(), 12 + 4 + 8);
+        let f: TargetVertexFormat = "P3f_N3f_G3f_B3f_T2f".parse().unwrap();
+        assert_eq!(f.get_size(), 4 * 12 + 8);
+
+        for s in [
+            "",
+            "N4b",
+            "P4h_X4b",
+            "P4h_N3b",
+            "P4h_T1h",
+            "P4h_N4bb",
+            // sizes and types the import cannot write
+            "P4f",
+            "P2h",
+            "P4b",
+            "P4c",
+            "P4h_N2h",
+            "P4h_G4f",
+            "P4h_B3h",
+            "P4h_T4b",
+            "P4h_C4b",
+            "P4h_T2h_T2h_T2h_T2h_T2h",
+        ] {
+            assert!(s.parse::<TargetVertexFormat>().is_err(), "{}", s);
+        }
     }
 }
@@ -13,15 +13,18 @@ use std::str;
 #[cfg(test)]
 mod tests {
     use super::*;
-    use rdm4lib::rdm_data_anim::RdAnimWriter2;
-    use rdm4lib::rdm_data_main::RdWriter2;
-    use rdm4lib::{gltf_export::GltfExportFormat, vertex::TargetVertexFormat};
+    use rdm4lib::gltf_export::{GltfExportFormat, GltfExportOptions};
+    use rdm4lib::gltf_reader::{AnimSampleRate, AnimSampling};
+    use rdm4lib::rdm_data_anim::{self, RdAnimWriter2};
+    use rdm4lib::rdm_data_main::{self, RdWriter2};
+    use rdm4lib::vertex::{TargetVertexFormat, VertexFormat2};
     use sha2::{Digest, Sha256};
+    use std::collections::HashMap;
     use std::convert::TryFrom;
     use std::fs;
     use std::path::PathBuf;
 
-    #[cfg(target_os = "windows")]
+    use rdm4lib::dds::DdsImage;
     use rdm4lib::rdm_material::RdMaterial;
 
     pub fn check_hash(path: &Path, expected: &str) {
@@ -41,6 +44,48 @@ mod tests {
         assert_eq!(rdm.vertex.len(), 3291);
         assert_eq!(rdm.triangle_indices.len() * 3, 7473);
         assert_eq!(rdm.mesh_info.len(), 2);
+        assert_eq!(rdm.bounding_box.min, [-1.5, -4.609375, -3.4042969]);
+        assert_eq!(rdm.bounding_box.max, [2.4667969, 1.4619141, 1.4443359]);
+        assert_eq!(rdm.bounding_box, rdm.vertex.bounding_box());
+        assert_eq!(rdm.materials.len(), 2);
+        assert_eq!(rdm.materials[0].name, "fishery");
+        assert_eq!(rdm.materials[1].name, "Material #879");
+        assert_eq!(
+            rdm.materials[1].texture_path,
+            "g:/graphic_backup/tobias/anno5/assets/buildings/others/harboursystem/system/harboursystem_front_diff.psd"
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn material_names_round_trip() {
+        let rdm = RdModell::from("rdm/fishery_others_lod2.rdm");
+        let names: Vec<String> = rdm.materials.iter().map(|m| m.name.clone()).collect();
+
+        let dir = Path::new("gltf_out_mat");
+        if !dir.exists() {
+            fs::create_dir(dir).unwrap();
+        }
+        gltf_export::build(rdm, Some(dir.into()), false, GltfExportFormat::GltfSeparate);
+
+        let i_gltf = gltf_reader::ImportedGltf::try_from(dir.join("out.gltf").as_path()).unwrap();
+        let rdm = gltf_reader::ImportedGltf::gltf_to_rdm(
+            &i_gltf,
+            "P4h_N4b_G4b_B4b_T2h".parse().unwrap(),
+            false,
+            false,
+            true,
+            None,
+        )
+        .unwrap();
+        let imported: Vec<String> = rdm.materials.iter().map(|m| m.name.clone()).collect();
+        assert_eq!(imported, names);
+
+        let materials = rdm.materials.clone();
+        let dir_dst = PathBuf::from("rdm_out/fishery");
+        std::fs::create_dir_all(&dir_dst).unwrap();
+        let dest_path = RdWriter2::new(rdm).write_rdm(Some(dir_dst), false);
+        assert_eq!(RdModell::from(&dest_path).materials, materials);
     }
 
     #[test]
@@ -61,12 +106,12 @@ mod tests {
 
         let output = if cfg!(target_os = "windows") {
             Command::new("..\\gltf_validator.exe")
-                .args(&["-ar", "gltf_out/out.gltf"])
+                .args(["-ar", "gltf_out/out.gltf"])
                 .output()
                 .expect("failed to execute process")
         } else {
             Command::new("../gltf_validator")
-                .args(&["-ar", "gltf_out/out.gltf"])
+                .args(["-ar", "gltf_out/out.gltf"])
                 .output()
                 .expect("failed to execute process")
         };
@@ -122,7 +167,6 @@ mod tests {
 
     #[test]
     #[cfg_attr(miri, ignore)]
-    #[cfg(target_os = "windows")]
     fn excavator_tycoons_lod1() {
         let mut rdm = RdModell::from("rdm/excavator_tycoons_lod1.rdm");
         rdm.mat = Some(RdMaterial::from(r"rdm/excavator_tycoons_diff_0.dds"));
@@ -148,7 +192,6 @@ mod tests {
 
     #[test]
     #[cfg_attr(miri, ignore)]
-    #[cfg(target_os = "windows")]
     fn residence_tier02_estate02() {
         let mut rdm = RdModell::from("rdm/residence_tier_02_estate_02_lod2.rdm");
         rdm.mat = Some(RdMaterial::new(vec![
@@ -171,6 +214,26 @@ mod tests {
         );
     }
 
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn decode_dds() {
+        let bc1 = DdsImage::try_from_path("rdm/excavator_tycoons_diff_0.dds").unwrap();
+        assert_eq!((bc1.width, bc1.height), (1024, 1024));
+        assert_eq!(bc1.rgba.len(), 1024 * 1024 * 4);
+
+        let bc7 = DdsImage::try_from_path("rdm/brick_wall_white_estate_01_diff_0.dds").unwrap();
+        assert_eq!((bc7.width, bc7.height), (512, 512));
+
+        let png = bc7.to_png().unwrap();
+        let decoder = png::Decoder::new(png.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buf).unwrap();
+        assert_eq!(buf, bc7.rgba);
+
+        assert!(DdsImage::try_from_path("rdm/basalt_crusher_others_lod2.rdm").is_err());
+    }
+
     #[test]
     #[cfg_attr(miri, ignore)]
     fn fishery_others_cutout_lod0() {
@@ -186,11 +249,154 @@ mod tests {
     fn ark_waterfall2() {
         let rdm = RdModell::from("rdm/ark_waterfall2.rdm");
         assert_eq!(rdm.vertex.len(), 105);
-        // TODO: cfg says P4h_N4b_T2h_C4c
-        assert_eq!(rdm.vertex.to_string(), "P4h_N4b_T2h_C4b");
+        assert_eq!(rdm.vertex.to_string(), "P4h_N4b_T2h_C4c");
         assert_eq!(rdm.mesh_info.len(), 1);
     }
 
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn vertex_color_round_trip() {
+        let rdm = RdModell::from("rdm/jungletree_big_01.rdm");
+        assert_eq!(rdm.vertex.to_string(), "P4h_N4b_G4b_B4b_T2h_C4c");
+        let colors = |rdm: &RdModell| -> Vec<u8> {
+            let size = rdm.vertex.get_size() as usize;
+            rdm.vertex
+                .as_bytes()
+                .chunks_exact(size)
+                .flat_map(|v| v[size - 4..].to_vec())
+                .collect()
+        };
+        let src_colors = colors(&rdm);
+
+        let dir = Path::new("gltf_out_color");
+        if !dir.exists() {
+            fs::create_dir(dir).unwrap();
+        }
+        gltf_export::build(rdm, Some(dir.into()), false, GltfExportFormat::GltfSeparate);
+
+        let i_gltf = gltf_reader::ImportedGltf::try_from(dir.join("out.gltf").as_path()).unwrap();
+        let rdm = gltf_reader::ImportedGltf::gltf_to_rdm(
+            &i_gltf,
+            "P4h_N4b_G4b_B4b_T2h_C4c".parse().unwrap(),
+            false,
+            false,
+            true,
+            None,
+        )
+        .unwrap();
+        assert_eq!(rdm.vertex.to_string(), "P4h_N4b_G4b_B4b_T2h_C4c");
+        assert_eq!(colors(&rdm), src_colors);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn multiple_uv_sets_round_trip() {
+        let mut rdm = RdModell::from("rdm/fishery_others_lod2.rdm");
+        assert_eq!(rdm.vertex.to_string(), "P4h_N4b_G4b_B4b_T2h");
+
+        // second uv set: u and v of the first set swapped
+        let mut buffer = Vec::with_capacity(rdm.vertex.len() as usize * 28);
+        for v in rdm.vertex.as_bytes().chunks_exact(24) {
+            buffer.extend_from_slice(v);
+            buffer.extend_from_slice(&[v[22], v[23], v[20], v[21]]);
+        }
+        let format: TargetVertexFormat = "P4h_N4b_G4b_B4b_T2h_T2h".parse().unwrap();
+        rdm.vertex = VertexFormat2::new(
+            format.identifiers.clone(),
+            rdm.vertex.len(),
+            format.get_size(),
+            buffer.into(),
+        );
+        let uvs = |rdm: &RdModell| -> Vec<u8> {
+            rdm.vertex
+                .as_bytes()
+                .chunks_exact(28)
+                .flat_map(|v| v[20..].to_vec())
+                .collect()
+        };
+        let src_uvs = uvs(&rdm);
+
+        let dir = Path::new("gltf_out_uv");
+        if !dir.exists() {
+            fs::create_dir(dir).unwrap();
+        }
+        gltf_export::build(rdm, Some(dir.into()), false, GltfExportFormat::GltfSeparate);
+
+        let i_gltf = gltf_reader::ImportedGltf::try_from(dir.join("out.gltf").as_path()).unwrap();
+        let rdm = gltf_reader::ImportedGltf::gltf_to_rdm(&i_gltf, format, false, false, true, None)
+            .unwrap();
+        assert_eq!(rdm.vertex.to_string(), "P4h_N4b_G4b_B4b_T2h_T2h");
+        assert_eq!(uvs(&rdm), src_uvs);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn bitangent_round_trip() {
+        let rdm = RdModell::from("rdm/fishery_others_lod2.rdm");
+        let format: TargetVertexFormat = rdm.vertex.to_string().parse().unwrap();
+        let bitangents = |rdm: &RdModell| -> Vec<u8> {
+            rdm.vertex
+                .as_bytes()
+                .chunks_exact(24)
+                .flat_map(|v| v[16..20].to_vec())
+                .collect()
+        };
+        let src_bitangents = bitangents(&rdm);
+
+        let dir = Path::new("gltf_out_bitangent");
+        if !dir.exists() {
+            fs::create_dir(dir).unwrap();
+        }
+        let options = GltfExportOptions {
+            raw_bitangent: true,
+        };
+        gltf_export::build_with_options(
+            rdm,
+            Some(dir.into()),
+            false,
+            GltfExportFormat::GltfSeparate,
+            &options,
+        );
+
+        let (gltf, buffers, _) = gltf::import(dir.join("out.gltf")).unwrap();
+        let primitive = gltf.meshes().next().unwrap().primitives().next().unwrap();
+        assert!(primitive
+            .get(&gltf::Semantic::Extras("BITANGENT".into()))
+            .is_some());
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+        let handedness: Vec<f32> = reader.read_tangents().unwrap().map(|t| t[3]).collect();
+        assert!(handedness.iter().all(|w| *w == 1.0 || *w == -1.0));
+
+        let i_gltf = gltf_reader::ImportedGltf::try_from(dir.join("out.gltf").as_path()).unwrap();
+        let rdm = gltf_reader::ImportedGltf::gltf_to_rdm(
+            &i_gltf,
+            format.clone(),
+            false,
+            false,
+            true,
+            None,
+        )
+        .unwrap();
+        assert_eq!(bitangents(&rdm), src_bitangents);
+
+        // mirrored like the normals and tangents
+        use nalgebra::Vector3;
+        let rdm = gltf_reader::ImportedGltf::gltf_to_rdm(&i_gltf, format, false, true, false, None)
+            .unwrap();
+        let decode = |b: &[u8]| -> Vector3<f32> {
+            Vector3::from_fn(|i, _| f32::from(b[i]) / 255.0 * 2.0 - 1.0)
+        };
+        let mirrored = bitangents(&rdm);
+        assert_eq!(mirrored.len(), src_bitangents.len());
+        for (src, dst) in src_bitangents.chunks(4).zip(mirrored.chunks(4)) {
+            let mut expected = decode(src);
+            expected.x = -expected.x;
+            if expected.norm() > 0.1 {
+                assert!(decode(dst).normalize().dot(&expected.normalize()) > 0.99);
+            }
+        }
+    }
+
     #[test]
     #[cfg_attr(miri, ignore)]
     fn exp_rdm_inv_basalt_crusher_others_lod0() {
@@ -200,13 +406,24 @@ mod tests {
         assert_eq!(rdm.vertex.to_string(), "P4h_N4b_G4b_B4b_T2h_I4b");
         assert_eq!(rdm.mesh_info.len(), 1);
 
+        assert_eq!(rdm.export_info.model_name, "basalt_crusher_others_lod2");
+        assert_eq!(
+            rdm.export_info.export_name2,
+            "Anno5_Building_Skin_1Blend.rmp"
+        );
+
+        let bounding_box = rdm.bounding_box;
+        let export_info = rdm.export_info.clone();
         let exp_rdm = RdWriter2::new(rdm);
         let dir_dst = PathBuf::from("rdm_out/basalt_crusher");
         std::fs::create_dir_all(&dir_dst).unwrap();
         let dest_path = exp_rdm.write_rdm(Some(dir_dst), false);
+        let written = RdModell::from(&dest_path);
+        assert_eq!(written.bounding_box, bounding_box);
+        assert_eq!(written.export_info, export_info);
         check_hash(
             &dest_path,
-            "2f6993eb99b4a0c89ee9723c822d18034cc772900331d1cb80e403b96339d398",
+            "41c9413e5d8a21716c81bf83309e7ab1ecc79843aa7859dbd2b1a0346252b99f",
         );
     }
 
@@ -217,17 +434,19 @@ mod tests {
         let i_gltf = gltf_reader::ImportedGltf::try_from(f_path).unwrap();
         let mut rdm = gltf_reader::ImportedGltf::gltf_to_rdm(
             &i_gltf,
-            TargetVertexFormat::P4h_N4b_G4b_B4b_T2h_I4b,
+            "P4h_N4b_G4b_B4b_T2h_I4b".parse().unwrap(),
             true,
             false,
             true,
             None,
-        );
+        )
+        .unwrap();
         assert_eq!(rdm.vertex.len(), 5184);
 
         let jj = rdm.joints.clone().unwrap();
         let mut anims =
-            gltf_reader::ImportedGltf::read_animation(&i_gltf, &jj, 6, 0.33333).unwrap();
+            gltf_reader::ImportedGltf::read_animation(&i_gltf, &jj, &Default::default(), &[])
+                .unwrap();
 
         assert_eq!(anims.len(), 1);
         let anim = anims.pop().unwrap();
@@ -253,12 +472,13 @@ mod tests {
                 "rdm/gltf/stormtrooper_with_tangent.gltf",
             ))
             .unwrap(),
-            TargetVertexFormat::P4h_N4b_G4b_B4b_T2h_I4b,
+            "P4h_N4b_G4b_B4b_T2h_I4b".parse().unwrap(),
             true,
             false,
             false,
             None,
-        );
+        )
+        .unwrap();
         assert_eq!(rdm.vertex.len(), 5184);
         assert_eq!(rdm.joints.as_ref().unwrap().len(), 72);
 
@@ -268,10 +488,42 @@ mod tests {
         let dest_path = exp_rdm.write_rdm(Some(dir_dst), false);
         check_hash(
             &dest_path,
-            "242c11e5a71a85fd25c5a398374cac4da6575e73b6f8abc963b05dc7f1fdd5db",
+            "565bbb8f718526016f4eebfce2ff03beae4f86762e40491de789d64495404493",
         );
     }
 
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn read_gltf_eight_influences() {
+        let format: TargetVertexFormat = "P4h_N4b_G4b_B4b_T2h_I4b_I4b_W4b_W4b".parse().unwrap();
+        let rdm = gltf_reader::ImportedGltf::gltf_to_rdm(
+            &gltf_reader::ImportedGltf::try_from(Path::new(
+                "rdm/gltf/stormtrooper_with_tangent.gltf",
+            ))
+            .unwrap(),
+            format,
+            true,
+            false,
+            false,
+            None,
+        )
+        .unwrap();
+        assert_eq!(rdm.vertex.len(), 5184);
+        for v in rdm.vertex.as_bytes().chunks_exact(40) {
+            let weight_sum: u32 = v[32..40].iter().map(|w| *w as u32).sum();
+            assert_eq!(weight_sum, 255);
+        }
+
+        let dir = Path::new("gltf_out_influences");
+        if !dir.exists() {
+            fs::create_dir(dir).unwrap();
+        }
+        gltf_export::build(rdm, Some(dir.into()), false, GltfExportFormat::GltfSeparate);
+        let gltf = fs::read_to_string(dir.join("out.gltf")).unwrap();
+        assert!(gltf.contains("JOINTS_1"));
+        assert!(gltf.contains("WEIGHTS_1"));
+    }
+
     #[test]
     #[cfg_attr(miri, ignore)]
     fn read_gltf_anim() {
@@ -279,42 +531,507 @@ mod tests {
         let i_gltf = gltf_reader::ImportedGltf::try_from(f_path).unwrap();
         let rdm = gltf_reader::ImportedGltf::gltf_to_rdm(
             &i_gltf,
-            TargetVertexFormat::P4h_N4b_G4b_B4b_T2h_I4b,
+            "P4h_N4b_G4b_B4b_T2h_I4b".parse().unwrap(),
             true,
             false,
             false,
             None,
-        );
+        )
+        .unwrap();
         assert_eq!(rdm.vertex.len(), 5184);
 
         let jj = &rdm.joints.unwrap();
         let mut anims =
-            gltf_reader::ImportedGltf::read_animation(&i_gltf, &jj, 6, 0.33333).unwrap();
+            gltf_reader::ImportedGltf::read_animation(&i_gltf, jj, &Default::default(), &[])
+                .unwrap();
 
         assert_eq!(anims.len(), 1);
         let anim = anims.pop().unwrap();
-        let exp_rdm = RdAnimWriter2::new(anim);
+        assert_eq!(anim.name, "mixamo.com");
+        let exp_rdm = RdAnimWriter2::new(
+            anim,
+            rdm_data_main::DEFAULT_MODEL_NAME,
+            rdm_data_main::DEFAULT_EXPORT_NAME,
+            rdm_data_anim::ANIM_UNKNOWN0_15,
+        )
+        .unwrap();
         let dir_dst = PathBuf::from("rdm_out/stormtrooper");
         std::fs::create_dir_all(&dir_dst).unwrap();
         exp_rdm.write_anim_rdm(Some(dir_dst), false);
 
         check_hash(
-            &PathBuf::from("rdm_out/stormtrooper/anim_0.rdm"),
-            "c2183cd7d5c342a6e6e1c8260b5ab93503fe2ad282d851bbee791bf3a1d6c3f1",
+            &PathBuf::from("rdm_out/stormtrooper/mixamo.com.rdm"),
+            "09ed283ec9e5efc46f9840aa475799a10fe95469c9f573c6ad99d9d7d8fbce8f",
         );
     }
 
     #[test]
     #[cfg_attr(miri, ignore)]
-    fn read_gltf_no_skin() {
+    fn read_gltf_anim_sampling() {
+        let f_path = Path::new("rdm/gltf/stormtrooper_with_tangent.gltf");
+        let i_gltf = gltf_reader::ImportedGltf::try_from(f_path).unwrap();
+        let rdm = gltf_reader::ImportedGltf::gltf_to_rdm(
+            &i_gltf,
+            "P4h_N4b_G4b_B4b_T2h_I4b".parse().unwrap(),
+            true,
+            false,
+            false,
+            None,
+        )
+        .unwrap();
+        let jj = rdm.joints.as_ref().unwrap();
+
+        let sampling = AnimSampling {
+            rate: AnimSampleRate::Fps(30.0),
+            start: Some(0.1),
+            end: Some(0.9),
+            resample: true,
+        };
+        let anims = gltf_reader::ImportedGltf::read_animation(&i_gltf, jj, &sampling, &[]).unwrap();
+        let anim = &anims[0];
+        assert_eq!(anim.time_max, 800);
+        assert_eq!(anim.anim_vec.len(), jj.len());
+        for joint in &anim.anim_vec {
+            assert_eq!(joint.frames.len(), 25);
+            assert_eq!(joint.frames[0].time, 0.0);
+            assert!((joint.frames[24].time - 0.8).abs() < 1e-5);
+        }
+
+        // without resampling only the time range is applied
+        let sampling = AnimSampling {
+            start: Some(0.1),
+            end: Some(0.9),
+            ..Default::default()
+        };
+        let anims = gltf_reader::ImportedGltf::read_animation(&i_gltf, jj, &sampling, &[]).unwrap();
+        for joint in &anims[0].anim_vec {
+            let last = joint.frames.last().unwrap().time;
+            assert_eq!(joint.frames[0].time, 0.0);
+            assert!((last - 0.8).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn read_gltf_anim_single_key() {
+        let f_path = Path::new("rdm/gltf/single_pose.gltf");
+        let i_gltf = gltf_reader::ImportedGltf::try_from(f_path).unwrap();
         let rdm = gltf_reader::ImportedGltf::gltf_to_rdm(
+            &i_gltf,
+            "P4h_N4b_I4b".parse().unwrap(),
+            true,
+            false,
+            true,
+            None,
+        )
+        .unwrap();
+        let jj = rdm.joints.as_ref().unwrap();
+
+        // every key at t=0
+        let mut anims =
+            gltf_reader::ImportedGltf::read_animation(&i_gltf, jj, &Default::default(), &[])
+                .unwrap();
+        let anim = anims.pop().unwrap();
+        assert_eq!(anim.time_max, 0);
+        assert_eq!(anim.anim_vec.len(), jj.len());
+        for joint in &anim.anim_vec {
+            assert_eq!(joint.frames.len(), 1);
+            assert_eq!(joint.frames[0].time, 0.0);
+        }
+
+        let dir_dst = PathBuf::from("rdm_out/single_pose");
+        std::fs::create_dir_all(&dir_dst).unwrap();
+        RdAnimWriter2::new(
+            anim,
+            rdm_data_main::DEFAULT_MODEL_NAME,
+            rdm_data_main::DEFAULT_EXPORT_NAME,
+            rdm_data_anim::ANIM_UNKNOWN0_15,
+        )
+        .unwrap()
+        .write_anim_rdm(Some(dir_dst), false);
+        let written = RdAnim::try_from_path("rdm_out/single_pose/pose.rdm").unwrap();
+        assert_eq!(written.time_max, 0);
+
+        // an empty time range also falls back to a single key
+        let sampling = AnimSampling {
+            start: Some(0.5),
+            end: Some(0.2),
+            ..Default::default()
+        };
+        let anims = gltf_reader::ImportedGltf::read_animation(&i_gltf, jj, &sampling, &[]).unwrap();
+        for joint in &anims[0].anim_vec {
+            assert_eq!(joint.frames.len(), 1);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn read_gltf_anim_unique_names() {
+        let f_path = Path::new("rdm/gltf/clip_names.gltf");
+        let i_gltf = gltf_reader::ImportedGltf::try_from(f_path).unwrap();
+        let rdm = gltf_reader::ImportedGltf::gltf_to_rdm(
+            &i_gltf,
+            "P4h_N4b_I4b".parse().unwrap(),
+            true,
+            false,
+            true,
+            None,
+        )
+        .unwrap();
+        let jj = rdm.joints.as_ref().unwrap();
+
+        let anims =
+            gltf_reader::ImportedGltf::read_animation(&i_gltf, jj, &Default::default(), &[])
+                .unwrap();
+        let names: Vec<&str> = anims.iter().map(|a| a.name.as_str()).collect();
+        // two clips "pose", "out" of the mesh, "po/se" and "po_se", an unnamed clip
+        assert_eq!(
+            names,
+            ["pose", "pose_1", "out_1", "po_se", "po_se_1", "anim_5"]
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn read_gltf_anim_selection() {
+        let f_path = Path::new("rdm/gltf/stormtrooper_with_tangent.gltf");
+        let i_gltf = gltf_reader::ImportedGltf::try_from(f_path).unwrap();
+        let rdm = gltf_reader::ImportedGltf::gltf_to_rdm(
+            &i_gltf,
+            "P4h_N4b_G4b_B4b_T2h_I4b".parse().unwrap(),
+            true,
+            false,
+            false,
+            None,
+        )
+        .unwrap();
+        let jj = rdm.joints.as_ref().unwrap();
+        let read = |selection: &[&str]| {
+            let selection: Vec<String> = selection.iter().map(|s| s.to_string()).collect();
+            gltf_reader::ImportedGltf::read_animation(&i_gltf, jj, &Default::default(), &selection)
+        };
+
+        assert_eq!(read(&["mixamo.com"]).unwrap()[0].name, "mixamo.com");
+        assert_eq!(read(&["work01", "0"]).unwrap().len(), 1);
+        assert!(read(&["work01"]).is_none());
+        assert!(read(&["1"]).is_none());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn multiple_anims_export() {
+        let mut rdm = RdModell::from("rdm/basalt_crusher_others_lod2.rdm");
+        rdm.add_skin();
+        rdm.add_anim(RdAnim::from("rdm/basalt_crusher_others_idle01.rdm"));
+        rdm.add_anim(RdAnim::from("rdm/basalt_crusher_others_work01.rdm"));
+        assert_eq!(rdm.anims().len(), 2);
+
+        let dir = Path::new("gltf_out_anims");
+        if !dir.exists() {
+            fs::create_dir(dir).unwrap();
+        }
+        gltf_export::build(rdm, Some(dir.into()), false, GltfExportFormat::GltfSeparate);
+
+        let gltf = gltf::Gltf::open(dir.join("out.gltf")).unwrap();
+        let names: Vec<&str> = gltf.animations().map(|a| a.name().unwrap()).collect();
+        assert_eq!(
+            names,
+            [
+                "basalt_crusher_others_idle01",
+                "basalt_crusher_others_work01"
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn remapped_duplicate_tracks() {
+        let mut anim = RdAnim::from("rdm/basalt_crusher_others_work01.rdm");
+        let tracks = anim.anim_vec.len();
+        let first = anim.anim_vec[0].name.clone();
+        let second = anim.anim_vec[1].name.clone();
+        let third = anim.anim_vec[2].name.clone();
+        let rotation = anim.anim_vec[0].frames[0].rotation;
+        assert_ne!(anim.anim_vec[1].frames[0].rotation, rotation);
+
+        // the first track replaces the second, the third is renamed to the same joint too
+        let remap = HashMap::from([(first, second.clone()), (third, second.clone())]);
+        anim.remap_tracks(&remap);
+        assert_eq!(anim.anim_vec.len(), tracks - 2);
+        let remapped: Vec<_> = anim.anim_vec.iter().filter(|t| t.name == second).collect();
+        assert_eq!(remapped.len(), 1);
+        assert_eq!(remapped[0].frames[0].rotation, rotation);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn unmatched_anim_tracks_are_skipped() {
+        let mut rdm = RdModell::from("rdm/basalt_crusher_others_lod2.rdm");
+        rdm.add_skin();
+        let mut anim = RdAnim::from("rdm/basalt_crusher_others_work01.rdm");
+        let tracks = anim.anim_vec.len();
+        let dropped = anim.anim_vec[0].name.clone();
+        let kept_frames: usize = anim.anim_vec[1..].iter().map(|t| t.frames.len()).sum();
+
+        let remap = HashMap::from([(dropped, "no_such_joint".to_owned())]);
+        anim.remap_tracks(&remap);
+        assert_eq!(anim.anim_vec[0].name, "no_such_joint");
+        rdm.add_anim(anim);
+
+        let dir = Path::new("gltf_out_unmatched");
+        if !dir.exists() {
+            fs::create_dir(dir).unwrap();
+        }
+        gltf_export::build(rdm, Some(dir.into()), false, GltfExportFormat::GltfSeparate);
+
+        let gltf = gltf::Gltf::open(dir.join("out.gltf")).unwrap();
+        let animation = gltf.animations().next().unwrap();
+        assert_eq!(animation.channels().count(), 2 * (tracks - 1));
+        // rotation + translation + time
+        let buffer = animation
+            .samplers()
+            .next()
+            .unwrap()
+            .input()
+            .view()
+            .unwrap()
+            .buffer();
+        assert_eq!(buffer.length(), kept_frames * (16 + 12 + 4));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn read_gltf_custom_vertex_format() {
+        let i_gltf = gltf_reader::ImportedGltf::try_from(Path::new(
+            "rdm/gltf/stormtrooper_with_tangent.gltf",
+        ))
+        .unwrap();
+        let reference = gltf_reader::ImportedGltf::gltf_to_rdm(
+            &i_gltf,
+            "P4h_N4b_G4b_B4b_T2h_I4b".parse().unwrap(),
+            true,
+            false,
+            false,
+            None,
+        )
+        .unwrap();
+
+        let format = "P3f_N4b_G4b_B4b_T2f_I4b_W4b";
+        let rdm = gltf_reader::ImportedGltf::gltf_to_rdm(
+            &i_gltf,
+            format.parse().unwrap(),
+            true,
+            false,
+            false,
+            None,
+        )
+        .unwrap();
+        assert_eq!(rdm.vertex.to_string(), format);
+        assert_eq!(rdm.vertex.get_size(), 12 + 3 * 4 + 8 + 4 + 4);
+        assert_eq!(rdm.vertex.len(), reference.vertex.len());
+        for i in 0..3 {
+            assert!((rdm.bounding_box.min[i] - reference.bounding_box.min[i]).abs() < 0.01);
+            assert!((rdm.bounding_box.max[i] - reference.bounding_box.max[i]).abs() < 0.01);
+        }
+
+        let dir_dst = PathBuf::from("rdm_out/stormtrooper_p3f");
+        std::fs::create_dir_all(&dir_dst).unwrap();
+        let dest_path = RdWriter2::new(rdm).write_rdm(Some(dir_dst), false);
+        let written = RdModell::from(&dest_path);
+        assert_eq!(written.vertex.to_string(), format);
+        assert_eq!(written.vertex.len(), reference.vertex.len());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn read_gltf_float_normals() {
+        let i_gltf = gltf_reader::ImportedGltf::try_from(Path::new(
+            "rdm/gltf/stormtrooper_with_tangent.gltf",
+        ))
+        .unwrap();
+        let format = "P3f_N3f_G3f_B3f_T2f";
+        let rdm = gltf_reader::ImportedGltf::gltf_to_rdm(
+            &i_gltf,
+            format.parse().unwrap(),
+            false,
+            false,
+            false,
+            None,
+        )
+        .unwrap();
+        assert_eq!(rdm.vertex.to_string(), format);
+        assert_eq!(rdm.vertex.get_size(), 4 * 12 + 8);
+
+        let dir_dst = PathBuf::from("rdm_out/stormtrooper_n3f");
+        std::fs::create_dir_all(&dir_dst).unwrap();
+        let dest_path = RdWriter2::new(rdm).write_rdm(Some(dir_dst), false);
+        let written = RdModell::from(&dest_path);
+        assert_eq!(written.vertex.to_string(), format);
+
+        // the raw bitangent is only exported for B4b
+        let dir = Path::new("gltf_out_n3f");
+        if !dir.exists() {
+            fs::create_dir(dir).unwrap();
+        }
+        let options = GltfExportOptions {
+            raw_bitangent: true,
+        };
+        gltf_export::build_with_options(
+            written,
+            Some(dir.into()),
+            false,
+            GltfExportFormat::GltfSeparate,
+            &options,
+        );
+        let (gltf, buffers, _) = gltf::import(dir.join("out.gltf")).unwrap();
+        let primitive = gltf.meshes().next().unwrap().primitives().next().unwrap();
+        assert!(primitive
+            .get(&gltf::Semantic::Extras("BITANGENT".into()))
+            .is_none());
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+        for n in reader.read_normals().unwrap() {
+            let len = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+            assert!((len - 1.0).abs() < 1e-3, "{:?}", n);
+        }
+        let handedness: Vec<f32> = reader.read_tangents().unwrap().map(|t| t[3]).collect();
+        assert!(handedness.iter().all(|w| *w == 1.0 || *w == -1.0));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn read_gltf_skinned_meshes() {
+        let read = |path: &str| {
+            let mut i_gltf = gltf_reader::ImportedGltf::try_from(Path::new(path)).unwrap();
+            i_gltf.all_meshes = true;
+            gltf_reader::ImportedGltf::gltf_to_rdm(
+                &i_gltf,
+                "P4h_N4b_I4b".parse().unwrap(),
+                true,
+                false,
+                false,
+                None,
+            )
+        };
+
+        // the translation of the second node of the skinned mesh is not applied, both instances
+        // share the vertices
+        let rdm = read("rdm/gltf/skinned_instances.gltf").unwrap();
+        assert_eq!(rdm.triangle_indices.len(), 2);
+        assert_eq!(rdm.vertex.len(), 3);
+        assert_eq!(rdm.joints.unwrap().len(), 2);
+        assert_eq!(rdm.bounding_box.max, [1.0, 2.0, 0.0]);
+
+        // the third mesh node is bound to another skin
+        assert!(read("rdm/gltf/two_skins.gltf").is_none());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn read_gltf_all_meshes() {
+        let mut i_gltf =
+            gltf_reader::ImportedGltf::try_from(Path::new("rdm/gltf/two_meshes.gltf")).unwrap();
+        i_gltf.all_meshes = true;
+        let rdm = gltf_reader::ImportedGltf::gltf_to_rdm(
+            &i_gltf,
+            "P4h_N4b_G4b_B4b_T2h".parse().unwrap(),
+            false,
+            false,
+            false,
+            None,
+        )
+        .unwrap();
+
+        // the second primitive of "roof" reuses the vertices of the first one
+        assert_eq!(rdm.vertex.len(), 6);
+        assert_eq!(rdm.triangle_indices.len(), 3);
+        let materials: Vec<u32> = rdm.mesh_info.iter().map(|m| m.material).collect();
+        assert_eq!(materials, vec![0, 1, 0]);
+        let names: Vec<&str> = rdm.materials.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["wall", "tiles"]);
+
+        // "child" is translated by its parent "root"
+        assert_eq!(rdm.bounding_box.min, [10.0, 0.0, 0.0]);
+        assert_eq!(rdm.bounding_box.max, [11.0, 6.0, 0.0]);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn read_gltf_primitive_modes() {
+        let rdm = gltf_reader::ImportedGltf::gltf_to_rdm(
+            &gltf_reader::ImportedGltf::try_from(Path::new("rdm/gltf/primitive_modes.gltf"))
+                .unwrap(),
+            "P4h_N4b_G4b_B4b_T2h".parse().unwrap(),
+            false,
+            false,
+            false,
+            None,
+        )
+        .unwrap();
+
+        // non-indexed strip and indexed fan of the same quad, the lines are skipped
+        assert_eq!(rdm.mesh_info.len(), 2);
+        assert_eq!(rdm.triangle_indices.len(), 4);
+        let names: Vec<&str> = rdm.materials.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["strip", "fan"]);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn optimize_gltf_import() {
+        let mut rdm = gltf_reader::ImportedGltf::gltf_to_rdm(
             &gltf_reader::ImportedGltf::try_from(Path::new("rdm/gltf/stormtrooper.gltf")).unwrap(),
-            TargetVertexFormat::P4h_N4b_G4b_B4b_T2h,
+            "P4h_N4b_G4b_B4b_T2h".parse().unwrap(),
+            false,
+            false,
+            false,
+            None,
+        )
+        .unwrap();
+        let triangles = rdm.triangle_indices.len();
+        let report = rdm.optimize();
+        assert_eq!(report.vertices_before, 5184);
+        assert!(report.acmr_after < report.acmr_before, "{:?}", report);
+
+        let dir_dst = PathBuf::from("rdm_out/optimize_gltf_import");
+        std::fs::create_dir_all(&dir_dst).unwrap();
+        let dest_path = RdWriter2::new(rdm).write_rdm(Some(dir_dst), false);
+        let written = RdModell::from(&dest_path);
+        assert_eq!(written.vertex.len(), report.vertices_after);
+        assert_eq!(written.triangle_indices.len(), triangles);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn read_gltf_lines_only() {
+        let i_gltf = gltf_reader::ImportedGltf::try_import(
+            Path::new("rdm/gltf/primitive_modes.gltf"),
+            1,
+            gltf_reader::ResolveNodeName::UniqueName,
+        )
+        .unwrap();
+        let rdm = gltf_reader::ImportedGltf::gltf_to_rdm(
+            &i_gltf,
+            "P4h_N4b_G4b_B4b_T2h".parse().unwrap(),
             false,
             false,
             false,
             None,
         );
+        assert!(rdm.is_none());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn read_gltf_no_skin() {
+        let rdm = gltf_reader::ImportedGltf::gltf_to_rdm(
+            &gltf_reader::ImportedGltf::try_from(Path::new("rdm/gltf/stormtrooper.gltf")).unwrap(),
+            "P4h_N4b_G4b_B4b_T2h".parse().unwrap(),
+            false,
+            false,
+            false,
+            None,
+        )
+        .unwrap();
         assert_eq!(rdm.vertex.len(), 5184);
 
         let exp_rdm = RdWriter2::new(rdm);
@@ -323,23 +1040,121 @@ mod tests {
         let dest_path = exp_rdm.write_rdm(Some(dir_dst), false);
         check_hash(
             &dest_path,
-            "8f08378928a2d5cfc80d10a54c7cde05644de7d9977f0eb262ecb6adcd37aa4e",
+            "5a22c4d662f09b8914aaa9707efaf00e848535a067b5860f5f6bea72c17373cc",
         );
     }
 
     #[test]
     #[cfg_attr(miri, ignore)]
-    #[ignore]
+    fn generated_tangents_match_exported() {
+        let format: TargetVertexFormat = "P4h_N4b_G4b_B4b_T2h".parse().unwrap();
+        let mut written = Vec::new();
+        for (src, dst) in [
+            ("rdm/gltf/stormtrooper.gltf", "rdm_out/tangents_generated"),
+            (
+                "rdm/gltf/stormtrooper_with_tangent.gltf",
+                "rdm_out/tangents_exported",
+            ),
+        ] {
+            let rdm = gltf_reader::ImportedGltf::gltf_to_rdm(
+                &gltf_reader::ImportedGltf::try_from(Path::new(src)).unwrap(),
+                format.clone(),
+                false,
+                false,
+                false,
+                None,
+            )
+            .unwrap();
+            let dir_dst = PathBuf::from(dst);
+            std::fs::create_dir_all(&dir_dst).unwrap();
+            written.push(fs::read(RdWriter2::new(rdm).write_rdm(Some(dir_dst), false)).unwrap());
+        }
+        // stormtrooper.gltf is stormtrooper_with_tangent.gltf without the TANGENT attribute.
+        // Only the rounding of a few G4b/B4b bytes differs from the Blender tangents.
+        assert_eq!(written[0].len(), written[1].len());
+        let diff: Vec<u8> = written[0]
+            .iter()
+            .zip(written[1].iter())
+            .map(|(a, b)| a.abs_diff(*b))
+            .filter(|&d| d != 0)
+            .collect();
+        assert!(diff.len() < 200, "{} bytes differ", diff.len());
+        assert!(diff.iter().all(|&d| d <= 3));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn write_gltf_textures_as_dds() {
+        let mut rdm = RdModell::from("rdm/residence_tier_02_estate_02_lod2.rdm");
+        rdm.mat = Some(RdMaterial::from(
+            "rdm/brick_wall_white_estate_01_diff_0.dds",
+        ));
+        let name = rdm.materials[0].name.clone();
+        let dir = PathBuf::from("gltf_out_textures");
+        fs::create_dir_all(&dir).unwrap();
+        gltf_export::build(rdm, Some(dir.clone()), false, GltfExportFormat::Glb);
+
+        let i_gltf = gltf_reader::ImportedGltf::try_from(dir.join("out.glb").as_path()).unwrap();
+        let dir_dst = PathBuf::from("rdm_out/textures");
+        fs::create_dir_all(&dir_dst).unwrap();
+        let written = i_gltf.write_textures(&dir_dst, false);
+        assert_eq!(written, vec![dir_dst.join(format!("{}_diff_0.dds", name))]);
+
+        let dds = DdsImage::try_from_path(&written[0]).unwrap();
+        assert_eq!((dds.width, dds.height), (512, 512));
+        let bytes = fs::read(&written[0]).unwrap();
+        // mip map count: 512 down to 1
+        assert_eq!(u32::from_le_bytes(bytes[28..32].try_into().unwrap()), 10);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn write_textures_same_material_names() {
+        let mut i_gltf =
+            gltf_reader::ImportedGltf::try_from(Path::new("rdm/gltf/same_material_names.gltf"))
+                .unwrap();
+        i_gltf.all_meshes = true;
+        i_gltf.textures = true;
+        let rdm = gltf_reader::ImportedGltf::gltf_to_rdm(
+            &i_gltf,
+            "P4h_N4b_G4b_B4b_T2h".parse().unwrap(),
+            false,
+            false,
+            false,
+            None,
+        )
+        .unwrap();
+        let texture_paths: Vec<&str> = rdm
+            .materials
+            .iter()
+            .map(|m| m.texture_path.as_str())
+            .collect();
+        assert_eq!(
+            texture_paths,
+            vec!["brick_diff_0.dds", "brick_1_diff_0.dds"]
+        );
+
+        let dir_dst = PathBuf::from("rdm_out/same_material_names");
+        let _ = fs::remove_dir_all(&dir_dst);
+        fs::create_dir_all(&dir_dst).unwrap();
+        let written = i_gltf.write_textures(&dir_dst, true);
+        let expected: Vec<PathBuf> = texture_paths.iter().map(|p| dir_dst.join(p)).collect();
+        assert_eq!(written, expected);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
     fn read_gltf_no_skin2_triangle() {
-        // no normals so ignore it !
+        // no normals: they are generated
         let rdm = gltf_reader::ImportedGltf::gltf_to_rdm(
             &gltf_reader::ImportedGltf::try_from(Path::new("rdm/gltf/triangle.gltf")).unwrap(),
-            TargetVertexFormat::P4h_N4b_G4b_B4b_T2h,
+            "P4h_N4b_G4b_B4b_T2h".parse().unwrap(),
             false,
             false,
             false,
             None,
-        );
+        )
+        .unwrap();
         assert_eq!(rdm.vertex.len(), 3);
 
         let exp_rdm = RdWriter2::new(rdm);
@@ -349,7 +1164,7 @@ mod tests {
         let dest_path = exp_rdm.write_rdm(Some(dir_dst), false);
         check_hash(
             &dest_path,
-            "f456d4418387e5bcbe4522064f5fdb75bd67b762793489ad6220a9718d431e3c",
+            "906826531401dcd4add1724557c182b275da7eb53d787052d38e6448d1398679",
         );
     }
 }
@@ -1,9 +1,13 @@
 extern crate rdm4lib;
 
-use rdm4lib::gltf_reader::ResolveNodeName;
-use rdm4lib::rdm_data_anim::RdAnimWriter2;
+use rdm4lib::gltf_reader::{AnimSampleRate, AnimSampling, NormalMode, ResolveNodeName};
+use rdm4lib::rdm_data_anim::{self, RdAnimWriter2};
 use rdm4lib::rdm_data_main::RdWriter2;
-use rdm4lib::{gltf_export::GltfExportFormat, vertex::TargetVertexFormat, RdModell};
+use rdm4lib::{
+    gltf_export::{GltfExportFormat, GltfExportOptions},
+    vertex::TargetVertexFormat,
+    RdModell,
+};
 
 use rdm4lib::gltf_export;
 use rdm4lib::rdm_anim::RdAnim;
@@ -13,10 +17,11 @@ use rdm4lib::{gltf_reader, rdm_material::RdMaterial};
 #[macro_use]
 extern crate log;
 
-use clap::Parser;
+use clap::{CommandFactory, ErrorKind, Parser};
 use env_logger::Env;
+use std::collections::HashMap;
 use std::ffi::OsStr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 fn cli_in_is_file(v: &OsStr) -> Result<(), String> {
     let p = PathBuf::from(v);
@@ -27,6 +32,22 @@ fn cli_in_is_file(v: &OsStr) -> Result<(), String> {
     }
 }
 
+fn cli_in_exists(v: &OsStr) -> Result<(), String> {
+    let p = PathBuf::from(v);
+    if p.exists() {
+        Ok(())
+    } else {
+        Err(format!("No such file or directory {}", v.to_string_lossy()))
+    }
+}
+
+fn cli_is_remap(v: &str) -> Result<(), String> {
+    match v.split_once('=') {
+        Some((track, joint)) if !track.is_empty() && !joint.is_empty() => Ok(()),
+        _ => Err(format!("Expected TRACK=JOINT, got {}", v)),
+    }
+}
+
 static HEADER_GLTF2RDM: &str = "GLTF TO RDM OPTIONS";
 static HEADER_RDM2GLTF: &str = "RDM TO GLTF OPTIONS";
 
@@ -79,7 +100,7 @@ struct Opts {
 
     // end of common options
     // start of HEADER_GLTF2RDM
-    /// VertexFormat for output rdm: P4h_N4b_G4b_B4b_T2h | P4h_N4b_G4b_B4b_T2h_I4b | P4h_N4b_G4b_B4b_T2h_I4b_W4b
+    /// VertexFormat for output rdm, e.g.: P4h_N4b_G4b_B4b_T2h | P4h_N4b_G4b_B4b_T2h_I4b | P4h_N4b_G4b_B4b_T2h_I4b_W4b | P4h_N4b_G4b_B4b_T2h_C4c. Any cfg VertexFormat made of up to 4 of each P4h|P3f, N4b|N3f, G4b|G3f, B4b|B3f, T2h|T2f, C4c, I4b, W4b is accepted
     #[clap(
         display_order(0),
         short = 'g',
@@ -99,6 +120,15 @@ struct Opts {
     )]
     gltf_mesh_index: u32,
 
+    /// Import every mesh of the glTF scene with its global node transform into one rdm. Ignores `--gltf-mesh-index`.
+    #[clap(
+        display_order(1),
+        long,
+        requires("gltf"),
+        help_heading = HEADER_GLTF2RDM
+    )]
+    gltf_all_meshes: bool,
+
     /// glTF to rdm: Do not apply node transforms. Recommended to use when working with animations.
     #[clap(
         display_order(2),
@@ -126,6 +156,134 @@ struct Opts {
     )]
     gltf_node_joint_name_src: ResolveNodeName,
 
+    /// Source file name stored in the written rdm files.
+    #[clap(
+        display_order(6),
+        long,
+        value_name("NAME"),
+        requires("gltf"),
+        help_heading = HEADER_GLTF2RDM
+    )]
+    export_name: Option<String>,
+
+    /// Export profile stored in the written rdm mesh. E.g. "Anno5_Building_Skin_1Blend.rmp"
+    #[clap(
+        display_order(7),
+        long,
+        value_name("NAME"),
+        requires("gltf"),
+        help_heading = HEADER_GLTF2RDM
+    )]
+    export_profile: Option<String>,
+
+    /// Model name of the written rdm mesh. Written animations reference the mesh by this name.
+    #[clap(
+        display_order(8),
+        long,
+        value_name("NAME"),
+        requires("gltf"),
+        help_heading = HEADER_GLTF2RDM
+    )]
+    model_name: Option<String>,
+
+    /// Keys per second of written animations. Default: 7 keys over the whole animation.
+    #[clap(
+        display_order(9),
+        long,
+        value_name("FPS"),
+        requires("animation"),
+        conflicts_with("anim-keys"),
+        help_heading = HEADER_GLTF2RDM
+    )]
+    anim_fps: Option<f32>,
+
+    /// Number of keys over the whole time range of written animations.
+    #[clap(
+        display_order(10),
+        long,
+        value_name("N"),
+        requires("animation"),
+        help_heading = HEADER_GLTF2RDM
+    )]
+    anim_keys: Option<usize>,
+
+    /// Start of the animation time range in seconds. Written animations start at 0.
+    #[clap(
+        display_order(11),
+        long,
+        value_name("SECONDS"),
+        requires("animation"),
+        help_heading = HEADER_GLTF2RDM
+    )]
+    anim_start: Option<f32>,
+
+    /// End of the animation time range in seconds. Default: last glTF keyframe.
+    #[clap(
+        display_order(12),
+        long,
+        value_name("SECONDS"),
+        requires("animation"),
+        help_heading = HEADER_GLTF2RDM
+    )]
+    anim_end: Option<f32>,
+
+    /// Resample every animation channel onto the keys set by `--anim-fps` or `--anim-keys`.
+    #[clap(
+        display_order(13),
+        long,
+        requires("animation"),
+        help_heading = HEADER_GLTF2RDM
+    )]
+    anim_resample: bool,
+
+    /// Only convert the glTF animations with these names or indices. Default: all animations.
+    #[clap(
+        display_order(14),
+        long,
+        value_name("NAME|INDEX"),
+        requires("animation"),
+        help_heading = HEADER_GLTF2RDM
+    )]
+    anim_select: Option<Vec<String>>,
+
+    /// Also encode the base color, normal and metallic textures as DDS (BC1/BC3 with mip maps) next to the rdm.
+    #[clap(
+        display_order(15),
+        long,
+        requires("gltf"),
+        help_heading = HEADER_GLTF2RDM
+    )]
+    textures: bool,
+
+    /// Normals computed for glTF meshes without normals: "smooth" | "flat"
+    #[clap(
+        display_order(16),
+        long,
+        value_name("smooth|flat"),
+        default_value = "smooth",
+        help_heading = HEADER_GLTF2RDM
+    )]
+    gltf_normals: NormalMode,
+
+    /// Smooth normals: faces with normals that differ by more than this angle (in degrees) get split vertices.
+    #[clap(
+        display_order(17),
+        long,
+        value_name("DEGREES"),
+        default_value = "60",
+        help_heading = HEADER_GLTF2RDM
+    )]
+    gltf_normal_angle: f32,
+
+    /// Weld identical vertices and reorder triangles and vertices for the GPU vertex cache. Logs the ACMR before and after.
+    #[clap(
+        display_order(18),
+        long,
+        requires("gltf"),
+        help_heading = HEADER_GLTF2RDM
+    )]
+    optimize: bool,
+
     // end of HEADER_GLTF2RDM
     // start of HEADER_RDM2GLTF
     /// Export format to use for rdm to gltf: "glb", "gltf", "gltfmin"
@@ -138,36 +296,61 @@ struct Opts {
     )]
     gltf_export_format: GltfExportFormat,
 
-    /// External animation file for rdm
+    /// External animation files for rdm. A directory adds every rdanimation inside it.
     #[clap(
         short = 'm',
         long = "rdanimation",
         display_order(1),
         value_name("anim/*.rdm"),
-        validator_os(cli_in_is_file),
+        validator_os(cli_in_exists),
         parse(from_str),
         conflicts_with("gltf"),
         requires_all(&["skeleton", "animation"]),
         help_heading = HEADER_RDM2GLTF
     )]
-    rdanimation: Option<PathBuf>,
+    rdanimation: Option<Vec<PathBuf>>,
+
+    /// Renames rdanimation tracks to match differently named joints, e.g. "bone_a=joint_a".
+    #[clap(
+        long,
+        value_name("TRACK=JOINT"),
+        display_order(2),
+        requires("rdanimation"),
+        validator(cli_is_remap),
+        help_heading = HEADER_RDM2GLTF
+    )]
+    rdanimation_remap: Option<Vec<String>>,
 
-    /// DiffuseTextures.
+    /// DiffuseTextures (BC1-BC5, BC7 or uncompressed DDS). Exported as PNG.
     #[clap(
         short = 't',
         long = "diffusetexture",
         value_name("*.dds"),
-        display_order(2),
+        display_order(3),
         validator_os(cli_in_is_file),
         parse(from_str),
         help_heading = HEADER_RDM2GLTF
     )]
     diffusetexture: Option<Vec<PathBuf>>,
+
+    /// Also export the raw bitangents as custom `_BITANGENT` attribute for a lossless glTF to rdm conversion.
+    #[clap(long, display_order(4), help_heading = HEADER_RDM2GLTF)]
+    raw_bitangent: bool,
     // end of HEADER_RDM2GLTF
 }
 
 fn main() {
     let opts: Opts = Opts::parse();
+    if let (Some(start), Some(end)) = (opts.anim_start, opts.anim_end) {
+        if start >= end {
+            Opts::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!("--anim-start {} has to be before --anim-end {}", start, end),
+                )
+                .exit();
+        }
+    }
     match opts.verbose {
         0 => env_logger::Builder::from_env(Env::default().default_filter_or("info")).init(),
         1 => env_logger::Builder::from_env(Env::default().default_filter_or("debug")).init(),
@@ -214,9 +397,18 @@ fn convert_rdm_to_gltf(opts: Opts) {
         info!("Skin added !");
     } else if opts.skeleton && opts.rdanimation.is_some() {
         rdm.add_skin();
-        let anim = RdAnim::from(opts.rdanimation.unwrap().as_path());
-        rdm.add_anim(anim);
-        info!("Skin and anim added !");
+        let remap: HashMap<String, String> = opts
+            .rdanimation_remap
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|m| m.split_once('='))
+            .map(|(track, joint)| (track.to_owned(), joint.to_owned()))
+            .collect();
+        for mut anim in read_rdanimations(&opts.rdanimation.unwrap()) {
+            anim.remap_tracks(&remap);
+            rdm.add_anim(anim);
+        }
+        info!("Skin and anims added !");
     } else {
         warn!("No skin. No anim !");
     }
@@ -226,35 +418,122 @@ fn convert_rdm_to_gltf(opts: Opts) {
     }
     info!("running gltf_export ...");
 
-    gltf_export::build(rdm, opts.out, !opts.force, opts.gltf_export_format);
+    let options = GltfExportOptions {
+        raw_bitangent: opts.raw_bitangent,
+    };
+    gltf_export::build_with_options(
+        rdm,
+        opts.out,
+        !opts.force,
+        opts.gltf_export_format,
+        &options,
+    );
+}
+
+fn read_rdanimations(paths: &[PathBuf]) -> Vec<RdAnim> {
+    let mut anims = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut files: Vec<PathBuf> = std::fs::read_dir(path)
+                .unwrap()
+                .map(|e| e.unwrap().path())
+                .filter(|p| p.is_file() && p.extension().is_some_and(|e| e == "rdm"))
+                .collect();
+            files.sort();
+            // the directory may also contain rdm meshes
+            for file in files {
+                match RdAnim::try_from_path(&file) {
+                    Ok(anim) => anims.push(anim),
+                    Err(e) => warn!("Skipping {:?}: {}", file, e),
+                }
+            }
+        } else {
+            anims.push(RdAnim::from(path.as_path()));
+        }
+    }
+    anims
 }
 
 fn convert_gltf_to_rdm(opts: Opts) {
     let f_path = opts.input.as_path();
-    let i_gltf = gltf_reader::ImportedGltf::try_import(
+    let mut i_gltf = gltf_reader::ImportedGltf::try_import(
         f_path,
         opts.gltf_mesh_index,
         opts.gltf_node_joint_name_src,
     )
     .unwrap();
+    i_gltf.all_meshes = opts.gltf_all_meshes;
+    i_gltf.normal_mode = opts.gltf_normals;
+    i_gltf.normal_angle = opts.gltf_normal_angle;
+    i_gltf.textures = opts.textures;
 
-    let rdm = gltf_reader::ImportedGltf::gltf_to_rdm(
+    let mut rdm = match gltf_reader::ImportedGltf::gltf_to_rdm(
         &i_gltf,
         opts.gltf.unwrap(),
         opts.skeleton,
         opts.negative_x_and_v0v2v1,
         opts.no_transform,
         opts.overide_mesh_idx,
-    );
+    ) {
+        Some(rdm) => rdm,
+        None => {
+            error!("Could not import a mesh from {}", f_path.display());
+            std::process::exit(1);
+        }
+    };
+    if let Some(export_name) = opts.export_name {
+        rdm.export_info.export_name1 = export_name;
+    }
+    if let Some(export_profile) = opts.export_profile {
+        rdm.export_info.export_name2 = export_profile;
+    }
+    if let Some(model_name) = opts.model_name {
+        rdm.export_info.model_name = model_name;
+    }
+    info!("{:?}", rdm.export_info);
+
+    if opts.optimize {
+        let report = rdm.optimize();
+        info!(
+            "Optimized: {} -> {} vertices, ACMR {:.3} -> {:.3}",
+            report.vertices_before, report.vertices_after, report.acmr_before, report.acmr_after
+        );
+    }
 
     if opts.skeleton && opts.animation {
         let jj = rdm.joints.as_ref().unwrap();
 
-        match gltf_reader::ImportedGltf::read_animation(&i_gltf, jj, 6, 0.33333) {
+        let mut sampling = AnimSampling {
+            start: opts.anim_start,
+            end: opts.anim_end,
+            resample: opts.anim_resample,
+            ..Default::default()
+        };
+        if let Some(fps) = opts.anim_fps {
+            sampling.rate = AnimSampleRate::Fps(fps);
+        } else if let Some(keys) = opts.anim_keys {
+            sampling.rate = AnimSampleRate::Keys(keys);
+        }
+        info!("{:?}", sampling);
+
+        match gltf_reader::ImportedGltf::read_animation(
+            &i_gltf,
+            jj,
+            &sampling,
+            opts.anim_select.as_deref().unwrap_or_default(),
+        ) {
             Some(mut anims) => {
                 for anim in anims.drain(..) {
-                    let exp_rdm = RdAnimWriter2::new(anim);
-                    exp_rdm.write_anim_rdm(opts.out.clone(), !opts.force);
+                    let name = anim.name.clone();
+                    match RdAnimWriter2::new(
+                        anim,
+                        &rdm.export_info.model_name,
+                        &rdm.export_info.export_name1,
+                        rdm_data_anim::ANIM_UNKNOWN0_15,
+                    ) {
+                        Ok(exp_rdm) => exp_rdm.write_anim_rdm(opts.out.clone(), !opts.force),
+                        Err(e) => error!("Could not write animation {}: {}", name, e),
+                    }
                 }
             }
             None => error!("Could not read animation. Does the glTF contain any animations ?"),
@@ -262,7 +541,11 @@ fn convert_gltf_to_rdm(opts: Opts) {
     }
 
     let exp_rdm = RdWriter2::new(rdm);
-    exp_rdm.write_rdm(opts.out, !opts.force);
+    let rdm_path = exp_rdm.write_rdm(opts.out, !opts.force);
+    if opts.textures {
+        let dir = rdm_path.parent().unwrap_or_else(|| Path::new("."));
+        i_gltf.write_textures(dir, !opts.force);
+    }
     if opts.skeleton && !opts.no_transform {
         error!("glTF skeleton is set, but no_transform is not! Animation & Mesh might be severely deformed! Use --no_transform and apply rotation & translation in the cfg file.");
     }